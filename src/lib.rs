@@ -1,14 +1,15 @@
-#![feature(test)]
+#![cfg_attr(test, feature(test))]
 
 mod primitives;
 mod raymarcher;
 pub mod scene;
 
-use crate::raymarcher::FindTargetSettings;
+use crate::raymarcher::{AdaptiveSettings, FindTargetSettings, SampleSettings};
 use crate::scene::scenemap::material::MaterialIndex;
-pub use primitives::{Color, Point3, Vec3};
-pub use raymarcher::render;
+pub use primitives::{Aabb, Color, Matrix4, Point3, Quaternion, Vec2, Vec3};
 pub use raymarcher::Ray;
+pub use raymarcher::{render, render_with_stats, RenderOutput, RenderStats};
+pub use raymarcher::{ReconstructionFilter, SamplePattern};
 
 pub struct Config {
     image_settings: ImageSettings,
@@ -39,6 +40,11 @@ pub struct RenderSettings {
     find_target_settings: FindTargetSettings,
     max_recursions: usize,
     material_override: Option<MaterialIndex>,
+    threads: usize,
+    tile_size: usize,
+    sampling: SampleSettings,
+    adaptive: Option<AdaptiveSettings>,
+    area_light_samples: usize,
 }
 
 impl RenderSettings {
@@ -53,6 +59,64 @@ impl RenderSettings {
             find_target_settings: FindTargetSettings::new(t_min, t_max, epsilon),
             max_recursions,
             material_override,
+            threads: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            tile_size: 16,
+            sampling: SampleSettings::default(),
+            adaptive: None,
+            area_light_samples: 16,
+        }
+    }
+
+    /// Number of worker threads used for rendering. Defaults to the available parallelism.
+    pub fn with_threads(self, threads: usize) -> Self {
+        Self {
+            threads: threads.max(1),
+            ..self
+        }
+    }
+
+    /// Side length in pixels of the square tiles handed out to worker threads.
+    pub fn with_tile_size(self, tile_size: usize) -> Self {
+        Self {
+            tile_size: tile_size.max(1),
+            ..self
+        }
+    }
+
+    /// Shoots `samples_per_pixel` rays per pixel, placed according to `pattern`, and combines
+    /// them with `filter`. Defaults to a single sample at the pixel position.
+    pub fn with_supersampling(
+        self,
+        samples_per_pixel: usize,
+        pattern: SamplePattern,
+        filter: ReconstructionFilter,
+    ) -> Self {
+        Self {
+            sampling: SampleSettings::new(samples_per_pixel, pattern, filter),
+            ..self
+        }
+    }
+
+    /// Number of shadow rays traced towards each area light per shading point. More samples
+    /// give smoother penumbrae. Defaults to 16.
+    pub fn with_area_light_samples(self, area_light_samples: usize) -> Self {
+        Self {
+            area_light_samples: area_light_samples.max(1),
+            ..self
+        }
+    }
+
+    /// Renders one sample per pixel, then recursively subdivides only those pixels whose
+    /// neighbours differ by more than `contrast_threshold` in any color channel, or hit a
+    /// different surface or material. Takes precedence over [Self::with_supersampling].
+    ///
+    /// Use [render_with_stats] to find out how many extra samples were spent.
+    pub fn with_adaptive_antialiasing(self, contrast_threshold: f64, max_depth: usize) -> Self {
+        Self {
+            adaptive: Some(AdaptiveSettings::new(contrast_threshold, max_depth)),
+            ..self
         }
     }
 }
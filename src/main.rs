@@ -3,15 +3,18 @@ use std::io::{BufWriter, Write};
 use std::time::Instant;
 
 use raymarcher_rs::scene::camera::Camera;
-use raymarcher_rs::scene::scenemap::lights::{AmbientLight, Light};
-use raymarcher_rs::scene::scenemap::material::{Material, MaterialList};
-use raymarcher_rs::scene::scenemap::sdf::combinators::{Difference, Intersect, Union};
-use raymarcher_rs::scene::scenemap::sdf::positioners::{Rotate, ScaleUniform, Translate};
-use raymarcher_rs::scene::scenemap::sdf::primitives::{Arbitrary, Cube, Sphere};
+use raymarcher_rs::scene::scenemap::lights::{AmbientLight, Attenuation, Light, PointLight};
+use raymarcher_rs::scene::scenemap::material::{Fresnel, Material, MaterialList};
+use raymarcher_rs::scene::scenemap::sdf::combinators::{Difference, Intersect, MultiUnion, Union};
+use raymarcher_rs::scene::scenemap::sdf::deformations::Displace;
+use raymarcher_rs::scene::scenemap::sdf::positioners::{ScaleUniform, Transform, Translate};
+use raymarcher_rs::scene::scenemap::sdf::primitives::{Cube, NegY, Sphere};
 use raymarcher_rs::scene::scenemap::sdf::WithMaterial;
 use raymarcher_rs::scene::scenemap::SceneMap;
 use raymarcher_rs::scene::{Scene, VerticalGradientBackground};
-use raymarcher_rs::{render, Color, Config, ImageSettings, Point3, RGBColor, RenderSettings, Vec3};
+use raymarcher_rs::{
+    render, Aabb, Color, Config, ImageSettings, Matrix4, Point3, RGBColor, RenderSettings, Vec3,
+};
 
 fn main() -> std::io::Result<()> {
     let start = Instant::now();
@@ -61,6 +64,12 @@ fn main() -> std::io::Result<()> {
         0.0,
     ));
 
+    let glass = material_list.insert(
+        Material::pure_transparent(1.5)
+            .with_fresnel(Fresnel::Dielectric)
+            .with_absorption(Color::new(0.8, 0.2, 0.8)),
+    );
+
     let config: Config = Config::new(
         ImageSettings::new(image_width, image_height),
         RenderSettings::new(0.001, 100.0, 1e-4, 100, None),
@@ -82,11 +91,11 @@ fn main() -> std::io::Result<()> {
     //     aspect_ratio,
     // );
 
-    let sine_wave = Arbitrary::new(|p| {
-        let v: &Vec3 = &p.0;
-        // Divide by 2 is to reduce holes in the floor at the cost of slower rendering
-        ((v.y - (v.x.sin() + v.z.sin())) / 2.0, None)
-    });
+    let sine_wave = Displace::new(
+        NegY,
+        |p: &Point3| -(p.0.x.sin() + p.0.z.sin()),
+        std::f64::consts::SQRT_2,
+    );
 
     let sine_wave = WithMaterial::new(ScaleUniform::new(sine_wave, 0.1), top_material);
 
@@ -100,24 +109,20 @@ fn main() -> std::io::Result<()> {
         Translate::new(ScaleUniform::new(&sine_wave, 2.0), Vec3::new(0.0, 0.5, 0.0)),
     );
 
-    let translate_cube = |v: Vec3| Cube::new(2.0, Point3(v * 0.2));
-
-    let cross = Union::new(
-        translate_cube(Vec3::new(1.0, 0.0, 0.0)),
-        Union::new(
-            translate_cube(Vec3::new(-1.0, 0.0, 0.0)),
-            Union::new(
-                translate_cube(Vec3::new(0.0, 1.0, 0.0)),
-                Union::new(
-                    translate_cube(Vec3::new(0.0, -1.0, 0.0)),
-                    Union::new(
-                        translate_cube(Vec3::new(0.0, 0.0, 1.0)),
-                        translate_cube(Vec3::new(0.0, 0.0, -1.0)),
-                    ),
-                ),
-            ),
-        ),
-    );
+    let cross = [
+        Vec3::new(1.0, 0.0, 0.0),
+        Vec3::new(-1.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, -1.0, 0.0),
+        Vec3::new(0.0, 0.0, 1.0),
+        Vec3::new(0.0, 0.0, -1.0),
+    ]
+    .iter()
+    .fold(MultiUnion::new(), |acc, v| {
+        let center = Point3(v * 0.2);
+        let bounds = Aabb::from_center(&center, &Vec3::new(1.0, 1.0, 1.0));
+        acc.with(bounds, Cube::new(2.0, center))
+    });
 
     let lattice = WithMaterial::new(
         Difference::new(Cube::default(), ScaleUniform::new(cross, 0.9)),
@@ -129,50 +134,54 @@ fn main() -> std::io::Result<()> {
         Translate::new(ScaleUniform::new(&sine_wave, 2.0), Vec3::new(0.0, 0.5, 0.0)),
     );
 
-    let contained_cube = ScaleUniform::new(
-        Rotate::new_degrees(
-            Union::new(lattice, wavy_cube),
-            22.5,
-            Vec3::new(0.0, 1.0, 0.0).unit(),
-        ),
-        0.9,
-    );
+    let contained_cube = Transform::new(
+        Union::new(lattice, wavy_cube),
+        Matrix4::rotation_euler(0.0, 22.5_f64.to_radians(), 0.0)
+            .then(&Matrix4::scaling(&Vec3::new(0.9, 0.9, 0.9)))
+            .then(&Matrix4::translation(&Vec3::new(-0.7, 1.9, -0.7))),
+    )
+    .expect("transform is invertible");
+
+    let glass_sphere = WithMaterial::new(Sphere::new(0.4, Point3::new(0.3, 1.4, 1.7)), glass);
 
     let sdf = Union::new(
         Union::new(
             WithMaterial::new(Cube::new(4.0, Point3::new(0.0, -1.0, 0.0)), red),
             Union::new(
                 Translate::new(wavy_sphere, Vec3::new(1.0, 1.5, 1.0)),
-                Translate::new(contained_cube, Vec3::new(-0.7, 1.9, -0.7)),
+                contained_cube,
             ),
         ),
-        floor,
+        Union::new(glass_sphere, floor),
     );
 
     let ambient_light = AmbientLight::new(Color::new(0.2, 0.2, 0.2));
 
     let lights = vec![
-        Light {
+        Light::Point(PointLight {
             location: Point3::new(0.0, 2.0, 10.0),
             specular: Color::new(0.4, 0.4, 0.4),
             diffuse: Color::new(0.4, 0.4, 0.4),
             strength: 6.0,
             shadow_hardness: 2.0,
-        },
-        Light {
+            attenuation: Attenuation::None,
+        }),
+        Light::Point(PointLight {
             location: Point3::new(0.0, 5.0, 0.0),
             specular: Color::new(0.4, 0.9, 0.4),
             diffuse: Color::new(0.4, 0.4, 0.4),
             strength: 2.0,
             shadow_hardness: 128.0,
-        },
-        Light {
+            attenuation: Attenuation::None,
+        }),
+        Light::Point(PointLight {
             location: Point3::new(3.0, 2.0, 1.5),
             specular: Color::new(0.5, 0.5, 0.5),
             diffuse: Color::new(0.9, 0.9, 0.9),
             strength: 3.0,
             shadow_hardness: 32.0,
-        },
+            attenuation: Attenuation::None,
+        }),
     ];
 
     let scene = Scene {
@@ -0,0 +1,165 @@
+use crate::primitives::{Point3, Vec3};
+
+/// Axis-aligned bounding box.
+#[derive(Debug, Clone)]
+pub struct Aabb {
+    pub min: Point3,
+    pub max: Point3,
+}
+
+impl Aabb {
+    /// Box that contains all of space. Nothing is ever culled against it.
+    pub const INFINITE: Self = Self {
+        min: Point3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        max: Point3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+    };
+
+    /// Box spanned by two opposite corners, in any order.
+    pub fn new(a: Point3, b: Point3) -> Self {
+        Self {
+            min: Point3(a.0.min(&b.0)),
+            max: Point3(a.0.max(&b.0)),
+        }
+    }
+
+    pub fn from_center(center: &Point3, half_extents: &Vec3) -> Self {
+        Self::new(center - half_extents, center + half_extents)
+    }
+
+    /// Smallest box containing all `points`, or [None] if there are none.
+    pub fn from_points<I: IntoIterator<Item = Point3>>(points: I) -> Option<Self> {
+        points.into_iter().fold(None, |acc, p| match acc {
+            None => Some(Self {
+                min: p.clone(),
+                max: p,
+            }),
+            Some(acc) => Some(Self {
+                min: Point3(acc.min.0.min(&p.0)),
+                max: Point3(acc.max.0.max(&p.0)),
+            }),
+        })
+    }
+
+    pub fn corners(&self) -> [Point3; 8] {
+        let (min, max) = (&self.min.0, &self.max.0);
+        [
+            Point3::new(min.x, min.y, min.z),
+            Point3::new(max.x, min.y, min.z),
+            Point3::new(min.x, max.y, min.z),
+            Point3::new(max.x, max.y, min.z),
+            Point3::new(min.x, min.y, max.z),
+            Point3::new(max.x, min.y, max.z),
+            Point3::new(min.x, max.y, max.z),
+            Point3::new(max.x, max.y, max.z),
+        ]
+    }
+
+    pub fn center(&self) -> Point3 {
+        Point3((&self.min.0 + &self.max.0) * 0.5)
+    }
+
+    pub fn size(&self) -> Vec3 {
+        &self.max.0 - &self.min.0
+    }
+
+    /// Whether the box is bounded in every direction.
+    pub fn is_finite(&self) -> bool {
+        let Vec3 { x, y, z } = self.size();
+        x.is_finite() && y.is_finite() && z.is_finite()
+    }
+
+    /// Whether the box is infinite on every side, so it doesn't bound anything.
+    pub fn is_unbounded(&self) -> bool {
+        let (min, max) = (&self.min.0, &self.max.0);
+        [min.x, min.y, min.z]
+            .iter()
+            .all(|&v| v == f64::NEG_INFINITY)
+            && [max.x, max.y, max.z].iter().all(|&v| v == f64::INFINITY)
+    }
+
+    /// Smallest box containing both `self` and `other`.
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: Point3(self.min.0.min(&other.min.0)),
+            max: Point3(self.max.0.max(&other.max.0)),
+        }
+    }
+
+    /// Overlap of `self` and `other`. If they don't overlap the result is inverted, with
+    /// `min` larger than `max` along some axis, which still contains nothing.
+    pub fn intersection(&self, other: &Self) -> Self {
+        Self {
+            min: Point3(self.min.0.max(&other.min.0)),
+            max: Point3(self.max.0.min(&other.max.0)),
+        }
+    }
+
+    pub fn translate(&self, v: &Vec3) -> Self {
+        Self {
+            min: &self.min + v,
+            max: &self.max + v,
+        }
+    }
+
+    /// Scales the box relative to the origin.
+    pub fn scale(&self, f: f64) -> Self {
+        Self::new(Point3(&self.min.0 * f), Point3(&self.max.0 * f))
+    }
+
+    /// Grows the box by `margin` on every side.
+    pub fn expand(&self, margin: f64) -> Self {
+        let m = Vec3::new(margin, margin, margin);
+        Self {
+            min: &self.min - &m,
+            max: &self.max + &m,
+        }
+    }
+
+    /// Euclidean distance from `p` to the box, or zero if `p` is inside.
+    ///
+    /// This is a lower bound on the distance to anything contained in the box.
+    pub fn distance_to(&self, p: &Point3) -> f64 {
+        self.distance_squared_to(p).sqrt()
+    }
+
+    /// Square of [Self::distance_to], which is cheaper to compute.
+    pub fn distance_squared_to(&self, p: &Point3) -> f64 {
+        let below = &self.min.0 - &p.0;
+        let above = &p.0 - &self.max.0;
+        below.max(&above).max(&Vec3::ZERO).length_squared()
+    }
+
+    /// Whether nothing inside the box can be closer to `p` than `best`, given that all SDFs
+    /// inside the box are bounded by it. If `p` is inside the box, anything inside may be
+    /// closer, otherwise only things closer than the box itself can be.
+    pub fn can_cull(&self, p: &Point3, best: f64) -> bool {
+        let d = self.distance_squared_to(p);
+        d > 0.0 && (best <= 0.0 || d >= best * best)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::ApproxEq;
+
+    use super::*;
+    use crate::test_constants::MARGIN;
+
+    #[test]
+    fn distance_is_zero_inside_and_euclidean_outside() {
+        let aabb = Aabb::new(Point3::new(1.0, 1.0, 1.0), Point3::new(-1.0, -1.0, -1.0));
+
+        assert!(aabb
+            .distance_to(&Point3::new(0.5, -0.5, 0.0))
+            .approx_eq(0.0, MARGIN));
+        assert!(aabb
+            .distance_to(&Point3::new(3.0, 0.0, 0.0))
+            .approx_eq(2.0, MARGIN));
+        assert!(aabb
+            .distance_to(&Point3::new(4.0, 5.0, 0.0))
+            .approx_eq(5.0, MARGIN));
+        assert!(Aabb::INFINITE
+            .distance_to(&Point3::new(1e10, 0.0, 0.0))
+            .approx_eq(0.0, MARGIN));
+    }
+}
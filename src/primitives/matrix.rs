@@ -0,0 +1,313 @@
+use std::ops::Mul;
+
+use crate::primitives::{Point3, Quaternion, UnitVec3, Vec3};
+
+/// 4x4 matrix in row-major order, used for affine transformations.
+///
+/// Matrices act on column vectors, so `a * b` applies `b` first. [Matrix4::then] composes
+/// in reading order instead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matrix4 {
+    m: [[f64; 4]; 4],
+}
+
+impl Matrix4 {
+    pub const IDENTITY: Self = Self::new([
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+
+    pub const fn new(rows: [[f64; 4]; 4]) -> Self {
+        Self { m: rows }
+    }
+
+    /// Affine matrix with the given 3x3 linear part and translation.
+    fn affine(linear: [[f64; 3]; 3], translation: &Vec3) -> Self {
+        let [r0, r1, r2] = linear;
+        Self::new([
+            [r0[0], r0[1], r0[2], translation.x],
+            [r1[0], r1[1], r1[2], translation.y],
+            [r2[0], r2[1], r2[2], translation.z],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn translation(v: &Vec3) -> Self {
+        Self::affine([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]], v)
+    }
+
+    pub fn scaling(f: &Vec3) -> Self {
+        Self::affine(
+            [[f.x, 0.0, 0.0], [0.0, f.y, 0.0], [0.0, 0.0, f.z]],
+            &Vec3::ZERO,
+        )
+    }
+
+    /// Shear where each coordinate gains a multiple of the other two, e.g. `xy` is how much
+    /// `x` moves per unit of `y`.
+    pub fn shearing(xy: f64, xz: f64, yx: f64, yz: f64, zx: f64, zy: f64) -> Self {
+        Self::affine([[1.0, xy, xz], [yx, 1.0, yz], [zx, zy, 1.0]], &Vec3::ZERO)
+    }
+
+    /// Rotation by a unit quaternion.
+    pub fn rotation(q: &Quaternion) -> Self {
+        let w = q.scalar();
+        let Vec3 { x, y, z } = q.vec();
+        Self::affine(
+            [
+                [
+                    1.0 - 2.0 * (y * y + z * z),
+                    2.0 * (x * y - w * z),
+                    2.0 * (x * z + w * y),
+                ],
+                [
+                    2.0 * (x * y + w * z),
+                    1.0 - 2.0 * (x * x + z * z),
+                    2.0 * (y * z - w * x),
+                ],
+                [
+                    2.0 * (x * z - w * y),
+                    2.0 * (y * z + w * x),
+                    1.0 - 2.0 * (x * x + y * y),
+                ],
+            ],
+            &Vec3::ZERO,
+        )
+    }
+
+    /// Counterclockwise rotation by `angle` radians around `axis`.
+    pub fn rotation_axis_angle(axis: UnitVec3, angle: f64) -> Self {
+        Self::rotation(&Quaternion::for_rotation(angle, axis))
+    }
+
+    /// Rotation by `x`, `y` and `z` radians around the respective axes, in that order.
+    pub fn rotation_euler(x: f64, y: f64, z: f64) -> Self {
+        Self::rotation_axis_angle(Vec3::new(1.0, 0.0, 0.0).unit(), x)
+            .then(&Self::rotation_axis_angle(
+                Vec3::new(0.0, 1.0, 0.0).unit(),
+                y,
+            ))
+            .then(&Self::rotation_axis_angle(
+                Vec3::new(0.0, 0.0, 1.0).unit(),
+                z,
+            ))
+    }
+
+    /// Moves the origin to `from` and turns the z axis towards `to`, keeping the y axis as
+    /// close to `up` as possible.
+    pub fn look_at(from: &Point3, to: &Point3, up: &Vec3) -> Self {
+        let z = (&to.0 - &from.0).unit().0;
+        let x = up.cross(&z).unit().0;
+        let y = z.cross(&x);
+        Self::affine([[x.x, y.x, z.x], [x.y, y.y, z.y], [x.z, y.z, z.z]], &from.0)
+    }
+
+    /// Applies `self`, then `next`.
+    pub fn then(&self, next: &Self) -> Self {
+        next * self
+    }
+
+    pub fn transform_point(&self, p: &Point3) -> Point3 {
+        let m = &self.m;
+        let Vec3 { x, y, z } = &p.0;
+        Point3::new(
+            m[0][0] * x + m[0][1] * y + m[0][2] * z + m[0][3],
+            m[1][0] * x + m[1][1] * y + m[1][2] * z + m[1][3],
+            m[2][0] * x + m[2][1] * y + m[2][2] * z + m[2][3],
+        )
+    }
+
+    /// Applies only the linear part, ignoring translation.
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+
+    /// Inverse by Gauss-Jordan elimination, or [None] if the matrix is singular.
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.m;
+        let mut inv = Self::IDENTITY.m;
+        for col in 0..4 {
+            let pivot = (col..4).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let scale = 1.0 / a[col][col];
+            for k in 0..4 {
+                a[col][k] *= scale;
+                inv[col][k] *= scale;
+            }
+            for row in 0..4 {
+                if row != col {
+                    let factor = a[row][col];
+                    for k in 0..4 {
+                        a[row][k] -= factor * a[col][k];
+                        inv[row][k] -= factor * inv[col][k];
+                    }
+                }
+            }
+        }
+        Some(Self::new(inv))
+    }
+
+    /// Smallest factor by which the linear part stretches any vector, i.e. its smallest
+    /// singular value.
+    ///
+    /// Found as the square root of the smallest eigenvalue of `AᵀA`, using the closed form
+    /// for symmetric 3x3 matrices.
+    pub fn min_stretch(&self) -> f64 {
+        let m = &self.m;
+        let mut ata = [[0.0; 3]; 3];
+        for (i, row) in ata.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = (0..3).map(|k| m[k][i] * m[k][j]).sum();
+            }
+        }
+
+        let p1 = ata[0][1].powi(2) + ata[0][2].powi(2) + ata[1][2].powi(2);
+        let trace = ata[0][0] + ata[1][1] + ata[2][2];
+        let smallest = if p1 == 0.0 {
+            ata[0][0].min(ata[1][1]).min(ata[2][2])
+        } else {
+            let q = trace / 3.0;
+            let p2 = (0..3).map(|i| (ata[i][i] - q).powi(2)).sum::<f64>() + 2.0 * p1;
+            let p = (p2 / 6.0).sqrt();
+            let b = |i: usize, j: usize| (ata[i][j] - if i == j { q } else { 0.0 }) / p;
+            let det_b = b(0, 0) * (b(1, 1) * b(2, 2) - b(1, 2) * b(2, 1))
+                - b(0, 1) * (b(1, 0) * b(2, 2) - b(1, 2) * b(2, 0))
+                + b(0, 2) * (b(1, 0) * b(2, 1) - b(1, 1) * b(2, 0));
+            let phi = (det_b / 2.0).clamp(-1.0, 1.0).acos() / 3.0;
+            q + 2.0 * p * (phi + 2.0 * std::f64::consts::FRAC_PI_3).cos()
+        };
+        smallest.max(0.0).sqrt()
+    }
+}
+
+impl Default for Matrix4 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Mul<&Matrix4> for &Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, rhs: &Matrix4) -> Self::Output {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+        Matrix4::new(m)
+    }
+}
+
+impl Mul<Matrix4> for Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, rhs: Matrix4) -> Self::Output {
+        &self * &rhs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::ApproxEq;
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::test_constants::MARGIN;
+
+    fn approx_eq(a: &Matrix4, b: &Matrix4) -> bool {
+        a.m.iter()
+            .flatten()
+            .zip(b.m.iter().flatten())
+            .all(|(x, y)| x.approx_eq(*y, MARGIN))
+    }
+
+    #[test]
+    fn rotation_matches_quaternion() {
+        let q = Quaternion::for_rotation(0.7, Vec3::new(1.0, 2.0, -1.0).unit());
+        let v = Vec3::new(0.3, -1.2, 2.0);
+
+        let rotated = (&q * Quaternion::new(0.0, v.clone()) * q.conjugate())
+            .vec()
+            .clone();
+        assert!(Matrix4::rotation(&q)
+            .transform_vector(&v)
+            .approx_eq(&rotated, MARGIN));
+    }
+
+    #[test]
+    fn then_applies_in_order() {
+        let m = Matrix4::scaling(&Vec3::new(2.0, 2.0, 2.0))
+            .then(&Matrix4::translation(&Vec3::new(1.0, 0.0, 0.0)));
+        let p = m.transform_point(&Point3::new(1.0, 1.0, 1.0));
+        assert!(p.approx_eq(&Point3::new(3.0, 2.0, 2.0), MARGIN));
+    }
+
+    #[test]
+    fn look_at_points_z_axis_at_target() {
+        let m = Matrix4::look_at(
+            &Point3::new(1.0, 0.0, 0.0),
+            &Point3::new(1.0, 0.0, -5.0),
+            &Vec3::new(0.0, 1.0, 0.0),
+        );
+        let p = m.transform_point(&Point3::new(0.0, 0.0, 2.0));
+        assert!(p.approx_eq(&Point3::new(1.0, 0.0, -2.0), MARGIN));
+    }
+
+    #[test]
+    fn singular_matrix_has_no_inverse() {
+        assert!(Matrix4::scaling(&Vec3::new(1.0, 0.0, 1.0))
+            .inverse()
+            .is_none());
+    }
+
+    #[test]
+    fn min_stretch_of_scaling_is_smallest_factor() {
+        let m = Matrix4::scaling(&Vec3::new(3.0, -0.5, 2.0));
+        assert!(m.min_stretch().approx_eq(0.5, MARGIN));
+        let rotated = m.then(&Matrix4::rotation_euler(0.3, 1.1, -0.4));
+        assert!(rotated.min_stretch().approx_eq(0.5, MARGIN));
+    }
+
+    prop_compose! {
+        fn arb_affine()(
+            euler in prop::array::uniform3(-3.0..3.0),
+            scale in prop::array::uniform3(0.2..3.0),
+            shear in prop::array::uniform6(-1.0..1.0),
+            t in prop::array::uniform3(-5.0..5.0),
+        ) -> Matrix4 {
+            Matrix4::shearing(shear[0], shear[1], shear[2], shear[3], shear[4], shear[5])
+                .then(&Matrix4::scaling(&Vec3::new(scale[0], scale[1], scale[2])))
+                .then(&Matrix4::rotation_euler(euler[0], euler[1], euler[2]))
+                .then(&Matrix4::translation(&Vec3::new(t[0], t[1], t[2])))
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn inverse_undoes_matrix(m in arb_affine()) {
+            if let Some(inv) = m.inverse() {
+                prop_assert!(approx_eq(&(&m * &inv), &Matrix4::IDENTITY));
+            }
+        }
+
+        #[test]
+        fn min_stretch_is_lower_bound(m in arb_affine(), v in prop::array::uniform3(-1.0..1.0)) {
+            let v = Vec3::new(v[0], v[1], v[2]);
+            prop_assert!(m.transform_vector(&v).length() >= m.min_stretch() * v.length() - 1e-9);
+        }
+    }
+}
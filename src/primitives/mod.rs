@@ -1,15 +1,27 @@
-use std::ops::{Add, Mul, Sub};
+use std::ops::{Add, Mul, Neg, Sub};
 
+mod aabb;
+mod matrix;
 mod quaternion;
 mod vec;
+pub use aabb::Aabb;
 #[cfg(test)]
 use float_cmp::{ApproxEq, F64Margin};
+pub use matrix::Matrix4;
 pub use quaternion::Quaternion;
-pub use vec::Vec3;
+pub use vec::{Vec2, Vec3};
 
 #[derive(Debug, Clone, derive_more::From, derive_more::Into, derive_more::AsRef)]
 pub struct UnitVec3(pub Vec3);
 
+impl Neg for &UnitVec3 {
+    type Output = UnitVec3;
+
+    fn neg(self) -> Self::Output {
+        UnitVec3(-&self.0)
+    }
+}
+
 #[derive(Debug, Clone, derive_more::From, derive_more::Into, derive_more::AsRef)]
 pub struct Point3(pub Vec3);
 
@@ -21,6 +33,14 @@ impl Point3 {
     }
 }
 
+impl Add<&Vec3> for &Point3 {
+    type Output = Point3;
+
+    fn add(self, rhs: &Vec3) -> Self::Output {
+        Point3(&self.0 + rhs)
+    }
+}
+
 impl Sub<&Vec3> for &Point3 {
     type Output = Point3;
 
@@ -30,7 +50,7 @@ impl Sub<&Vec3> for &Point3 {
 }
 
 #[cfg(test)]
-impl<'a> ApproxEq for &'a Point3 {
+impl ApproxEq for &Point3 {
     type Margin = F64Margin;
 
     fn approx_eq<M: Into<Self::Margin>>(self, other: Self, margin: M) -> bool {
@@ -38,7 +58,7 @@ impl<'a> ApproxEq for &'a Point3 {
     }
 }
 
-#[derive(Debug, Clone, derive_more::From, derive_more::Into, derive_more::AsRef)]
+#[derive(Debug, Clone, Default, derive_more::From, derive_more::Into, derive_more::AsRef)]
 pub struct Color(pub Vec3);
 
 impl Color {
@@ -52,6 +72,10 @@ impl Color {
         Self(Vec3 { x: r, y: g, z: b })
     }
 
+    pub const fn grey(v: f64) -> Self {
+        Self::new(v, v, v)
+    }
+
     pub fn r(&self) -> f64 {
         self.0.x
     }
@@ -73,6 +97,14 @@ impl Add<Color> for Color {
     }
 }
 
+impl Sub<Color> for Color {
+    type Output = Color;
+
+    fn sub(self, rhs: Color) -> Self::Output {
+        Self::Output::new(self.r() - rhs.r(), self.g() - rhs.g(), self.b() - rhs.b())
+    }
+}
+
 impl Mul<f64> for &Color {
     type Output = Color;
 
@@ -93,7 +125,15 @@ impl Mul<Color> for &Color {
     type Output = Color;
 
     fn mul(self, rhs: Color) -> Self::Output {
-        Color::new(self.r() * rhs.r(), self.g() * rhs.g(), self.g() * rhs.g())
+        Color::new(self.r() * rhs.r(), self.g() * rhs.g(), self.b() * rhs.b())
+    }
+}
+
+impl Mul<&Color> for Color {
+    type Output = Color;
+
+    fn mul(self, rhs: &Color) -> Self::Output {
+        &self * rhs
     }
 }
 
@@ -101,6 +141,20 @@ impl Mul<&Color> for &Color {
     type Output = Color;
 
     fn mul(self, rhs: &Color) -> Self::Output {
-        Color::new(self.r() * rhs.r(), self.g() * rhs.g(), self.g() * rhs.g())
+        Color::new(self.r() * rhs.r(), self.g() * rhs.g(), self.b() * rhs.b())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_product_is_per_channel() {
+        let a = Color::new(0.5, 0.25, 2.0);
+        let b = Color::new(4.0, 8.0, 0.125);
+        for product in [&a * &b, &a * b.clone(), a.clone() * &b].iter() {
+            assert_eq!((product.r(), product.g(), product.b()), (2.0, 2.0, 0.25));
+        }
     }
 }
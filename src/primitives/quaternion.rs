@@ -27,6 +27,10 @@ impl Quaternion {
         }
     }
 
+    pub fn scalar(&self) -> f64 {
+        self.a
+    }
+
     pub fn vec(&self) -> &Vec3 {
         &self.v
     }
@@ -5,7 +5,7 @@ use float_cmp::{ApproxEq, F64Margin};
 
 use crate::primitives::UnitVec3;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Vec3 {
     pub x: f64,
     pub y: f64,
@@ -57,6 +57,10 @@ impl Vec3 {
         self.x.max(self.y.max(self.z))
     }
 
+    pub fn min_component(&self) -> f64 {
+        self.x.min(self.y.min(self.z))
+    }
+
     pub fn max(&self, other: &Self) -> Self {
         Self {
             x: self.x.max(other.x),
@@ -65,13 +69,36 @@ impl Vec3 {
         }
     }
 
+    pub fn min(&self, other: &Self) -> Self {
+        Self {
+            x: self.x.min(other.x),
+            y: self.y.min(other.y),
+            z: self.z.min(other.z),
+        }
+    }
+
     pub fn reflect(&self, n: &UnitVec3) -> Self {
         self - &n.0 * 2.0 * self.dot(&n.0)
     }
+
+    /// Refracts this (unit length) direction through a surface with normal `n`, which must
+    /// point against the direction. `eta` is the ratio of the refractive index being left
+    /// to the one being entered.
+    ///
+    /// Returns [None] on total internal reflection.
+    pub fn refract(&self, n: &UnitVec3, eta: f64) -> Option<Self> {
+        let cos_i = -self.dot(&n.0);
+        let k = 1.0 - eta * eta * (1.0 - cos_i * cos_i);
+        if k < 0.0 {
+            None
+        } else {
+            Some(self * eta + &n.0 * (eta * cos_i - k.sqrt()))
+        }
+    }
 }
 
 #[cfg(test)]
-impl<'a> ApproxEq for &'a Vec3 {
+impl ApproxEq for &Vec3 {
     type Margin = F64Margin;
 
     fn approx_eq<T: Into<Self::Margin>>(self, other: Self, margin: T) -> bool {
@@ -298,6 +325,95 @@ impl Div<f64> for &Vec3 {
     }
 }
 
+/// Vector in the plane, used by 2D SDFs. Small enough to be [Copy], unlike [Vec3].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Vec2 {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Vec2 {
+    pub const ZERO: Self = Self::new(0.0, 0.0);
+
+    pub const fn new(x: f64, y: f64) -> Self {
+        Self { x, y }
+    }
+
+    pub fn dot(self, other: Self) -> f64 {
+        self.x.mul_add(other.x, self.y * other.y)
+    }
+
+    /// Z component of the 3D cross product, positive if `other` is counterclockwise from
+    /// `self`.
+    pub fn cross(self, other: Self) -> f64 {
+        self.x * other.y - self.y * other.x
+    }
+
+    pub fn length_squared(self) -> f64 {
+        self.dot(self)
+    }
+
+    pub fn length(self) -> f64 {
+        self.x.hypot(self.y)
+    }
+
+    pub fn abs(self) -> Self {
+        Self::new(self.x.abs(), self.y.abs())
+    }
+
+    pub fn max_component(self) -> f64 {
+        self.x.max(self.y)
+    }
+
+    pub fn max(self, other: Self) -> Self {
+        Self::new(self.x.max(other.x), self.y.max(other.y))
+    }
+
+    pub fn min(self, other: Self) -> Self {
+        Self::new(self.x.min(other.x), self.y.min(other.y))
+    }
+}
+
+impl Neg for Vec2 {
+    type Output = Vec2;
+
+    fn neg(self) -> Self::Output {
+        Self::new(-self.x, -self.y)
+    }
+}
+
+impl Add<Vec2> for Vec2 {
+    type Output = Vec2;
+
+    fn add(self, rhs: Vec2) -> Self::Output {
+        Self::new(self.x + rhs.x, self.y + rhs.y)
+    }
+}
+
+impl Sub<Vec2> for Vec2 {
+    type Output = Vec2;
+
+    fn sub(self, rhs: Vec2) -> Self::Output {
+        Self::new(self.x - rhs.x, self.y - rhs.y)
+    }
+}
+
+impl Mul<f64> for Vec2 {
+    type Output = Vec2;
+
+    fn mul(self, rhs: f64) -> Self::Output {
+        Self::new(self.x * rhs, self.y * rhs)
+    }
+}
+
+impl Div<f64> for Vec2 {
+    type Output = Vec2;
+
+    fn div(self, rhs: f64) -> Self::Output {
+        Self::new(self.x / rhs, self.y / rhs)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     extern crate test;
@@ -401,6 +517,30 @@ mod tests {
         assert!(a.dot(&(&b + &c)).approx_eq(a.dot(&b) + a.dot(&c), MARGIN));
     }
 
+    #[test]
+    fn refract_head_on_keeps_direction() {
+        let d = Vec3::new(0.0, -1.0, 0.0);
+        let n = Vec3::new(0.0, 1.0, 0.0).unit();
+        assert!(d.refract(&n, 1.0 / 1.5).unwrap().approx_eq(&d, MARGIN));
+    }
+
+    #[test]
+    fn refract_follows_snell() {
+        let d = Vec3::new(1.0, -1.0, 0.0).unit().0;
+        let n = Vec3::new(0.0, 1.0, 0.0).unit();
+        let eta = 1.0 / 1.5;
+        let t = d.refract(&n, eta).unwrap();
+        assert!(t.length().approx_eq(1.0, MARGIN));
+        assert!(t.x.approx_eq(d.x * eta, MARGIN));
+    }
+
+    #[test]
+    fn refract_total_internal_reflection() {
+        let d = Vec3::new(1.0, -0.2, 0.0).unit().0;
+        let n = Vec3::new(0.0, 1.0, 0.0).unit();
+        assert!(d.refract(&n, 1.5).is_none());
+    }
+
     #[bench]
     fn length(b: &mut Bencher) {
         b.iter(|| {
@@ -0,0 +1,153 @@
+//! Adaptive anti-aliasing.
+//!
+//! Every pixel first gets a single sample. Pixels that differ too much from one of their
+//! neighbours, either in color or in what the camera ray hit, are then split into quadrants
+//! which are sampled separately. Quadrants that still disagree are split again, up to
+//! [AdaptiveSettings::max_depth] times.
+
+use itertools::Itertools;
+
+use crate::raymarcher::{for_each_pixel, render_sample, RenderOutput, RenderStats, Sample};
+use crate::scene::Scene;
+use crate::{Color, Config, ImageSettings};
+
+#[derive(Debug, Clone)]
+pub struct AdaptiveSettings {
+    /// Largest per-channel color difference between neighbouring samples that does not
+    /// trigger a refinement.
+    pub contrast_threshold: f64,
+    /// Maximum number of times a pixel is split into quadrants.
+    pub max_depth: usize,
+}
+
+impl AdaptiveSettings {
+    pub fn new(contrast_threshold: f64, max_depth: usize) -> Self {
+        Self {
+            contrast_threshold,
+            max_depth: max_depth.max(1),
+        }
+    }
+
+    fn differ(&self, a: &Sample, b: &Sample) -> bool {
+        a.surface != b.surface || contrast(&a.color, &b.color) > self.contrast_threshold
+    }
+}
+
+pub(super) fn render(settings: &AdaptiveSettings, config: &Config, scene: &Scene) -> RenderOutput {
+    let ImageSettings { width, height } = config.image_settings;
+
+    let initial = for_each_pixel(config, |i, j| {
+        render_sample(i as f64, j as f64, config, scene)
+    });
+
+    let refined = for_each_pixel(config, |i, j| {
+        let row = height - 1 - j;
+        let sample = &initial[row * width + i];
+        let neighbours = [
+            (row > 0).then(|| (row - 1, i)),
+            (row + 1 < height).then(|| (row + 1, i)),
+            (i > 0).then(|| (row, i - 1)),
+            (i + 1 < width).then(|| (row, i + 1)),
+        ];
+        let on_edge = neighbours
+            .iter()
+            .flatten()
+            .any(|(r, c)| settings.differ(sample, &initial[r * width + c]));
+
+        if on_edge {
+            let mut extra_samples = 0;
+            let color = refine(
+                i as f64,
+                j as f64,
+                0.5,
+                1,
+                settings,
+                config,
+                scene,
+                &mut extra_samples,
+            );
+            (color, extra_samples)
+        } else {
+            (sample.color.clone(), 0)
+        }
+    });
+
+    let stats = RenderStats {
+        refined_pixels: refined.iter().filter(|(_, extra)| *extra > 0).count(),
+        extra_samples: refined.iter().map(|(_, extra)| extra).sum(),
+    };
+
+    RenderOutput {
+        pixels: refined.into_iter().map(|(c, _)| c).collect(),
+        stats,
+    }
+}
+
+/// Samples the four quadrants of the square centered on `(x, y)` and recurses into them
+/// if they disagree.
+#[allow(clippy::too_many_arguments)]
+fn refine(
+    x: f64,
+    y: f64,
+    half_size: f64,
+    depth: usize,
+    settings: &AdaptiveSettings,
+    config: &Config,
+    scene: &Scene,
+    extra_samples: &mut usize,
+) -> Color {
+    let q = half_size / 2.0;
+    let offsets = [(-q, -q), (q, -q), (-q, q), (q, q)];
+    let samples: Vec<Sample> = offsets
+        .iter()
+        .map(|(dx, dy)| render_sample(x + dx, y + dy, config, scene))
+        .collect();
+    *extra_samples += samples.len();
+
+    let disagree = depth < settings.max_depth
+        && samples
+            .iter()
+            .tuple_combinations()
+            .any(|(a, b)| settings.differ(a, b));
+
+    let sum = if disagree {
+        offsets
+            .iter()
+            .map(|(dx, dy)| {
+                refine(
+                    x + dx,
+                    y + dy,
+                    q,
+                    depth + 1,
+                    settings,
+                    config,
+                    scene,
+                    extra_samples,
+                )
+            })
+            .fold(Color::BLACK, |acc, c| acc + c)
+    } else {
+        samples
+            .into_iter()
+            .fold(Color::BLACK, |acc, s| acc + s.color)
+    };
+
+    sum * (1.0 / offsets.len() as f64)
+}
+
+/// Largest per-channel difference between two colors.
+fn contrast(a: &Color, b: &Color) -> f64 {
+    (a.0.clone() - &b.0).abs().max_component()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contrast_is_largest_channel_difference() {
+        let a = Color::new(0.1, 0.5, 0.9);
+        let b = Color::new(0.2, 0.1, 0.8);
+        assert!((contrast(&a, &b) - 0.4).abs() < 1e-12);
+    }
+}
@@ -1,41 +1,169 @@
-use itertools::Itertools;
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
 
+pub use adaptive::AdaptiveSettings;
 pub use ray::FindTargetSettings;
 pub use ray::Ray;
+pub use sampling::{ReconstructionFilter, SamplePattern, SampleSettings};
 
-use crate::primitives::{Color, UnitVec3};
+use crate::primitives::Color;
 use crate::raymarcher::ray::FindTargetResult;
-use crate::scene::scenemap::lights::Light;
-use crate::scene::scenemap::material::Material;
+use crate::raymarcher::tile::Tile;
+use crate::scene::scenemap::lights::{Light, LightSample};
+use crate::scene::scenemap::material::{Fresnel, Material, MaterialIndex};
 use crate::scene::scenemap::sdf::Sdf;
 use crate::scene::scenemap::SceneMap;
 use crate::scene::Scene;
 use crate::{Config, ImageSettings, Point3, RenderSettings, Vec3};
 
+mod adaptive;
 mod ray;
+mod refraction;
+mod sampling;
+mod tile;
 
+/// Renders the scene using [RenderSettings::with_threads] worker threads.
+///
+/// Pixels are returned in scanline order, starting at the top left.
 pub fn render<'a>(config: &'a Config, scene: &'a Scene<'a>) -> impl Iterator<Item = Color> + 'a {
+    render_with_stats(config, scene).pixels.into_iter()
+}
+
+/// Like [render], but also reports how much work the renderer did.
+pub fn render_with_stats(config: &Config, scene: &Scene) -> RenderOutput {
+    match &config.render_settings.adaptive {
+        Some(adaptive) => adaptive::render(adaptive, config, scene),
+        None => RenderOutput {
+            pixels: for_each_pixel(config, |i, j| render_pixel(i, j, config, scene)),
+            stats: RenderStats::default(),
+        },
+    }
+}
+
+pub struct RenderOutput {
+    /// Pixels in scanline order, starting at the top left.
+    pub pixels: Vec<Color>,
+    pub stats: RenderStats,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RenderStats {
+    /// Pixels that adaptive anti-aliasing decided to refine.
+    pub refined_pixels: usize,
+    /// Samples traced on top of the initial sample per pixel by adaptive anti-aliasing.
+    pub extra_samples: usize,
+}
+
+/// Evaluates `f` for every pixel in parallel and returns the results in scanline order.
+///
+/// `f` receives pixel coordinates with `j` counting up from the bottom row, matching the
+/// camera's `v` axis. The image is split into tiles which are picked up by the worker threads
+/// as they become idle.
+fn for_each_pixel<T, F>(config: &Config, f: F) -> Vec<T>
+where
+    T: Send + Clone + Default,
+    F: Fn(usize, usize) -> T + Sync,
+{
     let ImageSettings { width, height } = config.image_settings;
-    let height_iter = (0..height).rev();
-    let width_iter = 0..width;
+    let RenderSettings {
+        threads, tile_size, ..
+    } = config.render_settings;
 
-    let pixels = height_iter.cartesian_product(width_iter);
+    let tiles = Tile::split(width, height, tile_size);
+    let next_tile = AtomicUsize::new(0);
 
-    pixels.map(move |(j, i)| {
-        let i = i as f64;
-        let j = j as f64;
+    let rendered: Vec<(usize, Vec<T>)> = thread::scope(|s| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                s.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let idx = next_tile.fetch_add(1, Ordering::Relaxed);
+                        let tile = match tiles.get(idx) {
+                            Some(tile) => tile,
+                            None => break done,
+                        };
+                        let pixels = tile.pixels().map(|(i, j)| f(i, height - 1 - j)).collect();
+                        done.push((idx, pixels));
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|w| w.join().expect("Render thread panicked"))
+            .collect()
+    });
 
-        let u = i / ((width as f64) - 1.0);
-        let v = j / ((height as f64) - 1.0);
+    let mut image = vec![T::default(); width * height];
+    for (idx, pixels) in rendered {
+        tiles[idx].blit(pixels, &mut image, width);
+    }
 
-        let ray = scene.camera.get_ray(u, v);
-        generate_pixel(
-            &ray,
-            &config.render_settings,
-            scene,
-            config.render_settings.max_recursions,
-        )
-    })
+    image
+}
+
+/// Traces all samples of pixel `(i, j)` and takes their average weighted by the filter.
+fn render_pixel(i: usize, j: usize, config: &Config, scene: &Scene) -> Color {
+    let sampling = &config.render_settings.sampling;
+
+    let (sum, total_weight) = sampling.offsets(i, j).into_iter().fold(
+        (Color::BLACK, 0.0),
+        |(sum, total_weight), (dx, dy)| {
+            let weight = sampling.filter.weight(dx, dy);
+            let c = render_sample(i as f64 + dx, j as f64 + dy, config, scene).color;
+            (sum + c * weight, total_weight + weight)
+        },
+    );
+
+    sum * (1.0 / total_weight)
+}
+
+/// Result of tracing a single camera ray.
+#[derive(Debug, Clone, Default)]
+pub struct Sample {
+    pub color: Color,
+    pub surface: Surface,
+}
+
+/// What a camera ray hit first.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Surface {
+    #[default]
+    Background,
+    Hit(Option<MaterialIndex>),
+}
+
+/// Traces a single camera ray through image position `(x, y)`, in pixel units.
+fn render_sample(x: f64, y: f64, config: &Config, scene: &Scene) -> Sample {
+    let ImageSettings { width, height } = config.image_settings;
+
+    let u = x / ((width as f64) - 1.0);
+    let v = y / ((height as f64) - 1.0);
+
+    let ray = scene.camera.get_ray(u, v);
+    let render_settings = &config.render_settings;
+    if render_settings.max_recursions == 0 {
+        return Sample::default();
+    }
+
+    match ray.find_target(&render_settings.find_target_settings, scene.scene_map.sdf) {
+        Some(target) => Sample {
+            surface: Surface::Hit(target.material.map(|m| m.dominant())),
+            color: shade(
+                &ray,
+                target,
+                render_settings,
+                scene,
+                render_settings.max_recursions,
+            ),
+        },
+        None => Sample {
+            color: scene.background.value_at(&ray),
+            surface: Surface::Background,
+        },
+    }
 }
 
 fn generate_pixel<'a>(
@@ -47,52 +175,82 @@ fn generate_pixel<'a>(
     if remaining_depth == 0 {
         return Color::BLACK;
     }
-    let Scene {
-        scene_map,
-        background,
-        ..
-    } = scene;
 
+    ray.find_target(&render_settings.find_target_settings, scene.scene_map.sdf)
+        .map(|target| shade(ray, target, render_settings, scene, remaining_depth))
+        .unwrap_or_else(|| scene.background.value_at(ray))
+}
+
+fn shade<'a>(
+    ray: &Ray,
+    target: FindTargetResult,
+    render_settings: &'a RenderSettings,
+    scene: &'a Scene<'a>,
+    remaining_depth: usize,
+) -> Color {
+    let FindTargetResult {
+        point, material, ..
+    } = target;
+    let scene_map = &scene.scene_map;
     let sdf = scene_map.sdf;
 
-    ray.find_target(&render_settings.find_target_settings, scene_map.sdf)
-        .map(
-            |FindTargetResult {
-                 point,
-                 material_index,
-             }| {
-                let mat = if let Some(m) = &render_settings.material_override {
-                    Some(m)
-                } else {
-                    material_index.as_ref()
-                };
-                let material = mat
-                    .and_then(|m| scene_map.materials.get(*m))
-                    .unwrap_or(&Material::DEFAULT);
-
-                let phong_contribution = phong(
-                    ray,
-                    material,
-                    scene,
-                    &point,
-                    &render_settings.find_target_settings,
-                );
+    let blend = match render_settings.material_override {
+        Some(m) => Some(m.into()),
+        None => material,
+    };
+    let material = blend
+        .and_then(|b| scene_map.materials.resolve(&b))
+        .unwrap_or(Cow::Borrowed(&Material::DEFAULT));
+    let material = material.as_ref();
 
-                let reflection_contribution = if material.reflectivity() > 0.0 {
-                    if let Some(child) = material.child_ray(sdf, &point, &ray) {
-                        generate_pixel(&child, render_settings, scene, remaining_depth - 1)
-                            * material.reflectivity()
-                    } else {
-                        Color::BLACK
-                    }
-                } else {
-                    Color::BLACK
-                };
+    // Without Fresnel terms the contributions are simply scaled by constant factors,
+    // otherwise reflection takes its share first and the rest is split between
+    // refraction and Phong shading.
+    let (reflectance, transmittance, phong_weight) = match material.fresnel() {
+        Fresnel::None => (
+            Color::grey(material.reflectivity()),
+            Color::grey(material.transparency()),
+            Color::WHITE,
+        ),
+        _ => {
+            let normal = sdf.estimate_normal(&point);
+            let cos_i = -ray.direction().as_ref().dot(normal.as_ref());
+            let reflectance = material.reflectance(cos_i);
+            let remaining = Color::WHITE - reflectance.clone();
+            (
+                reflectance,
+                &remaining * material.transparency(),
+                remaining * (1.0 - material.transparency()),
+            )
+        }
+    };
 
-                phong_contribution + reflection_contribution
-            },
-        )
-        .unwrap_or_else(|| background.value_at(&ray))
+    let phong_contribution = phong(ray, material, scene, &point, render_settings) * &phong_weight;
+
+    let reflection_contribution = if reflectance.0.max_component() > 0.0 {
+        if let Some(child) = material.child_ray(sdf, &point, ray) {
+            generate_pixel(&child, render_settings, scene, remaining_depth - 1) * &reflectance
+        } else {
+            Color::BLACK
+        }
+    } else {
+        Color::BLACK
+    };
+
+    let refraction_contribution = if material.transparency() > 0.0 {
+        refraction::refraction_contribution(
+            ray,
+            &point,
+            material,
+            render_settings,
+            scene,
+            remaining_depth,
+        ) * &transmittance
+    } else {
+        Color::BLACK
+    };
+
+    phong_contribution + reflection_contribution + refraction_contribution
 }
 
 fn phong<'a>(
@@ -100,7 +258,7 @@ fn phong<'a>(
     material: &'a Material,
     scene: &'a Scene<'a>,
     point: &Point3,
-    find_target_settings: &FindTargetSettings,
+    render_settings: &RenderSettings,
 ) -> Color {
     let Scene {
         scene_map, camera, ..
@@ -120,49 +278,217 @@ fn phong<'a>(
     let v = (camera.origin.as_ref() - point.as_ref()).unit();
 
     let ambient_color = &ambient_light.0;
-    let light_factors = find_light_factors(lights, point, *sdf, find_target_settings);
+    let light_factors = find_light_factors(lights, point, *sdf, render_settings);
     let ambient_contribution = ambient_color * material.ambient();
 
     let light_contribution = light_factors
-        .map(|(light, factor)| {
-            let l: UnitVec3 = (light.location.as_ref() - point.as_ref()).unit();
-            let l_dot_normal: f64 = l.as_ref().dot(normal.as_ref());
-            let r: Vec3 = normal.as_ref() * (2.0 * (l_dot_normal)) - l.as_ref();
-            let diffuse = if l_dot_normal > 0.0 {
-                &material.diffuse() * &light.diffuse * l_dot_normal
-            } else {
-                Color::BLACK
-            };
-            let specular_dot = r.dot(v.as_ref());
-            let specular = if specular_dot > 0.0 && l_dot_normal > 0.0 {
-                &material.specular() * &light.specular * specular_dot.powf(material.shininess())
-            } else {
-                Color::BLACK
-            };
-            (diffuse + specular) * factor
-        })
+        .map(
+            |(
+                light,
+                LightSample {
+                    direction: l,
+                    distance,
+                    ..
+                },
+                factor,
+            )| {
+                let l_dot_normal: f64 = l.as_ref().dot(normal.as_ref());
+                let r: Vec3 = normal.as_ref() * (2.0 * (l_dot_normal)) - l.as_ref();
+                let diffuse = if l_dot_normal > 0.0 {
+                    &material.diffuse() * light.diffuse() * l_dot_normal
+                } else {
+                    Color::BLACK
+                };
+                let specular_dot = r.dot(v.as_ref());
+                let specular = if specular_dot > 0.0 && l_dot_normal > 0.0 {
+                    &material.specular()
+                        * light.specular()
+                        * specular_dot.powf(material.shininess())
+                } else {
+                    Color::BLACK
+                };
+                (diffuse + specular) * (factor * light.attenuation().factor(distance))
+            },
+        )
         .fold(Color::BLACK, |acc, c| acc + c);
 
     ambient_contribution + light_contribution
 }
 
+/// Direction towards each light and how much of it reaches `p`.
+///
+/// Area lights are sampled with [RenderSettings::with_area_light_samples] shadow rays, each
+/// carrying an equal share of the light. Shadow rays towards lights that are infinitely far
+/// away stop at `t_max`.
 fn find_light_factors<'a>(
     lights: &'a [Light],
     p: &'a Point3,
     sdf: &'a dyn Sdf,
-    find_target_settings: &'a FindTargetSettings,
-) -> impl Iterator<Item = (&'a Light, f64)> + 'a {
-    lights.iter().map(move |l| {
-        let direction = l.location.as_ref() - p.as_ref();
-        let settings = FindTargetSettings {
-            t_max: direction.length(),
-            ..find_target_settings.clone()
+    render_settings: &'a RenderSettings,
+) -> impl Iterator<Item = (&'a Light, LightSample, f64)> + 'a {
+    let find_target_settings = &render_settings.find_target_settings;
+    lights.iter().flat_map(move |l| {
+        let uvs = if l.is_area() {
+            sampling::unit_square_samples(p, render_settings.area_light_samples)
+        } else {
+            vec![(0.5, 0.5)]
         };
-        let origin = p.clone();
-        let r = Ray::new(origin, direction.unit());
-        (
-            l,
-            l.strength * r.soft_shadow(&settings, sdf, l.shadow_hardness),
-        )
+        let share = 1.0 / uvs.len() as f64;
+
+        uvs.into_iter().map(move |uv| {
+            let sample = l.sample(p, uv);
+            // Skip the shadow march for points the light can't reach anyway.
+            if sample.intensity <= 0.0 || l.attenuation().factor(sample.distance) <= 0.0 {
+                return (l, sample, 0.0);
+            }
+            let settings = FindTargetSettings {
+                t_max: sample.distance.min(find_target_settings.t_max),
+                ..find_target_settings.clone()
+            };
+            let r = Ray::new(p.clone(), sample.direction.clone());
+            let factor = l.strength()
+                * sample.intensity
+                * share
+                * r.soft_shadow(&settings, sdf, l.shadow_hardness());
+            (l, sample, factor)
+        })
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::camera::Camera;
+    use crate::scene::scenemap::lights::{
+        AmbientLight, AreaLight, AreaLightShape, Attenuation, DirectionalLight, PointLight,
+    };
+    use crate::scene::scenemap::material::MaterialList;
+    use crate::scene::scenemap::sdf::primitives::Sphere;
+    use crate::scene::VerticalGradientBackground;
+
+    fn with_test_scene(f: impl FnOnce(&Scene)) {
+        let sdf = Sphere::new(1.0, Point3::ORIGIN);
+        let materials = MaterialList::new();
+        let lights = vec![Light::Point(PointLight {
+            location: Point3::new(0.0, 2.0, 5.0),
+            specular: Color::new(0.4, 0.4, 0.4),
+            diffuse: Color::new(0.4, 0.4, 0.4),
+            strength: 1.0,
+            shadow_hardness: 8.0,
+            attenuation: Attenuation::None,
+        })];
+        let scene = Scene {
+            camera: Camera::new(
+                Point3::ORIGIN,
+                Point3::new(0.0, 0.0, 5.0),
+                Vec3::new(0.0, 1.0, 0.0),
+                45.0,
+                1.5,
+            ),
+            scene_map: SceneMap {
+                sdf: &sdf,
+                materials: &materials,
+                ambient_light: AmbientLight::new(Color::new(0.2, 0.2, 0.2)),
+                lights: &lights,
+            },
+            background: Box::new(VerticalGradientBackground {
+                from: Color::WHITE,
+                to: Color::new(0.5, 0.7, 1.0),
+            }),
+        };
+        f(&scene)
+    }
+
+    #[test]
+    fn threaded_render_matches_single_threaded() {
+        with_test_scene(|scene| {
+            let render_with = |threads, tile_size| {
+                let config = Config::new(
+                    ImageSettings::new(30, 20),
+                    RenderSettings::new(0.001, 100.0, 1e-4, 5, None)
+                        .with_threads(threads)
+                        .with_tile_size(tile_size),
+                );
+                render(&config, scene).map(|c| c.0).collect::<Vec<_>>()
+            };
+
+            let single = render_with(1, 64);
+            let threaded = render_with(4, 7);
+
+            assert_eq!(single.len(), 30 * 20);
+            assert!(single
+                .iter()
+                .zip(threaded.iter())
+                .all(|(a, b)| a.x == b.x && a.y == b.y && a.z == b.z));
+        })
+    }
+
+    #[test]
+    fn adaptive_antialiasing_only_refines_edges() {
+        with_test_scene(|scene| {
+            let config = Config::new(
+                ImageSettings::new(30, 20),
+                RenderSettings::new(0.001, 100.0, 1e-4, 5, None).with_adaptive_antialiasing(0.1, 2),
+            );
+            let RenderOutput { pixels, stats } = render_with_stats(&config, scene);
+
+            assert_eq!(pixels.len(), 30 * 20);
+            assert!(stats.refined_pixels > 0);
+            assert!(stats.refined_pixels < 30 * 20 / 2);
+            assert!(stats.extra_samples >= 4 * stats.refined_pixels);
+        })
+    }
+
+    #[test]
+    fn directional_light_shadows_reach_t_max() {
+        let sun = [Light::Directional(DirectionalLight {
+            direction: Vec3::new(0.0, -1.0, 0.0).unit(),
+            specular: Color::WHITE,
+            diffuse: Color::WHITE,
+            strength: 2.0,
+            angular_size: 0.0,
+        })];
+        let settings = RenderSettings::new(0.001, 100.0, 1e-5, 1, None);
+        let p = Point3::ORIGIN;
+
+        let occluder = Sphere::new(1.0, Point3::new(0.0, 50.0, 0.0));
+        let (_, sample, factor) = find_light_factors(&sun, &p, &occluder, &settings)
+            .next()
+            .unwrap();
+        assert!(sample.distance.is_infinite());
+        assert_eq!(factor, 0.0);
+
+        let out_of_reach = Sphere::new(1.0, Point3::new(0.0, 200.0, 0.0));
+        let (_, _, factor) = find_light_factors(&sun, &p, &out_of_reach, &settings)
+            .next()
+            .unwrap();
+        assert_eq!(factor, 2.0);
+    }
+
+    #[test]
+    fn area_light_casts_penumbra() {
+        let lamp = [Light::Area(AreaLight {
+            shape: AreaLightShape::Disk {
+                center: Point3::new(0.0, 10.0, 0.0),
+                normal: Vec3::new(0.0, -1.0, 0.0).unit(),
+                radius: 1.0,
+            },
+            specular: Color::WHITE,
+            diffuse: Color::WHITE,
+            strength: 1.0,
+            attenuation: Attenuation::None,
+        })];
+        let settings = RenderSettings::new(0.001, 100.0, 1e-5, 1, None).with_area_light_samples(64);
+        let occluder = Sphere::new(1.0, Point3::new(0.0, 5.0, 0.0));
+        let light_reaching = |x: f64| {
+            find_light_factors(&lamp, &Point3::new(x, 0.0, 0.0), &occluder, &settings)
+                .map(|(_, _, factor)| factor)
+                .sum::<f64>()
+        };
+
+        assert_eq!(light_reaching(0.0), 0.0);
+        let penumbra = light_reaching(1.5);
+        assert!(penumbra > 0.0 && penumbra < 1.0);
+        assert!(light_reaching(10.0) > 0.7);
+    }
+}
@@ -1,5 +1,5 @@
 use crate::primitives::UnitVec3;
-use crate::scene::scenemap::material::MaterialIndex;
+use crate::scene::scenemap::material::MaterialBlend;
 use crate::scene::scenemap::sdf::Sdf;
 use crate::{Point3, Vec3};
 use itertools::FoldWhile::{Continue, Done};
@@ -35,13 +35,13 @@ impl Ray {
         find_target_settings: &FindTargetSettings,
         sdf: &dyn Sdf,
     ) -> Option<FindTargetResult> {
-        DepthIterator::new(sdf, &self, find_target_settings.t_min)
-            .into_iter()
+        DepthIterator::new(sdf, self, find_target_settings.t_min)
             .take_while(|DepthResult { total_depth, .. }| total_depth < &find_target_settings.t_max)
             .find(|DepthResult { dist, .. }| *dist < find_target_settings.epsilon)
             .map(|dr| FindTargetResult {
                 point: dr.point,
-                material_index: dr.mat_idx,
+                material: dr.material,
+                distance: dr.total_depth,
             })
     }
 
@@ -55,12 +55,18 @@ impl Ray {
         let corrected_t_max =
             find_target_settings.t_max * (1.0 - 3.0 * find_target_settings.epsilon);
 
-        DepthIterator::new(sdf, &self, find_target_settings.t_min)
-            .into_iter()
+        DepthIterator::new(sdf, self, find_target_settings.t_min)
             .take_while(|DepthResult { total_depth, .. }| *total_depth < corrected_t_max)
             .fold_while(1.0, |acc: f64, sr| {
                 if sr.dist < find_target_settings.epsilon {
-                    Done(acc)
+                    // With infinite hardness the penumbra never darkens, so an occluder has to
+                    // block the light outright. A hit right at the start is the surface the
+                    // ray leaves from.
+                    if k.is_infinite() && sr.total_depth > find_target_settings.t_min {
+                        Done(0.0)
+                    } else {
+                        Done(acc)
+                    }
                 } else {
                     Continue(acc.min(k * sr.dist / sr.total_depth))
                 }
@@ -88,7 +94,9 @@ impl FindTargetSettings {
 
 pub struct FindTargetResult {
     pub point: Point3,
-    pub material_index: Option<MaterialIndex>,
+    pub material: Option<MaterialBlend>,
+    /// Distance travelled along the ray to reach `point`.
+    pub distance: f64,
 }
 
 struct DepthIterator<'a> {
@@ -113,7 +121,7 @@ impl<'a> DepthIterator<'a> {
 struct DepthResult {
     point: Point3,
     dist: f64,
-    mat_idx: Option<MaterialIndex>,
+    material: Option<MaterialBlend>,
     total_depth: f64,
 }
 
@@ -123,7 +131,7 @@ impl<'a> Iterator for DepthIterator<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         let total_depth = self.prev_dist + self.cur_depth;
         let point = Point3(self.r.origin.as_ref() + self.r.direction.as_ref() * total_depth);
-        let (dist, mat_idx) = self.sdf.value_at(&point);
+        let (dist, material) = self.sdf.value_at(&point);
 
         self.cur_depth = total_depth;
         self.prev_dist = dist;
@@ -131,7 +139,7 @@ impl<'a> Iterator for DepthIterator<'a> {
         Some(Self::Item {
             point,
             dist,
-            mat_idx,
+            material,
             total_depth,
         })
     }
@@ -181,6 +189,45 @@ mod tests {
         assert!(ray.find_target(&settings, &sdf).is_none());
     }
 
+    /// Soft shadow as accumulated along the ray, up to and including the first hit.
+    fn penumbra(ray: &Ray, settings: &FindTargetSettings, sdf: &dyn Sdf, k: f64) -> f64 {
+        let mut acc: f64 = 1.0;
+        for sr in DepthIterator::new(sdf, ray, settings.t_min) {
+            if sr.total_depth >= settings.t_max * (1.0 - 3.0 * settings.epsilon)
+                || sr.dist < settings.epsilon
+            {
+                break;
+            }
+            acc = acc.min(k * sr.dist / sr.total_depth);
+        }
+        acc
+    }
+
+    #[test]
+    fn blocked_shadow_ray_keeps_penumbra() {
+        let settings = FindTargetSettings::new(0.0, 20.0, 1e-5);
+        let sdf = Sphere::default();
+        let blocked = Ray::new_unnormalized(Point3::new(-10.0, 0.9, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let grazing = Ray::new_unnormalized(Point3::new(-10.0, 1.2, 0.0), Vec3::new(1.0, 0.0, 0.0));
+
+        for ray in [blocked, grazing].iter() {
+            let shadow = ray.soft_shadow(&settings, &sdf, 8.0);
+            assert!(shadow > 0.0);
+            assert_eq!(shadow, penumbra(ray, &settings, &sdf, 8.0));
+        }
+    }
+
+    #[test]
+    fn infinitely_hard_shadow_is_blocked_by_hit() {
+        let settings = FindTargetSettings::new(0.0, 20.0, 1e-5);
+        let sdf = Sphere::default();
+        let blocked = Ray::new_unnormalized(Point3::new(-10.0, 0.9, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let grazing = Ray::new_unnormalized(Point3::new(-10.0, 1.2, 0.0), Vec3::new(1.0, 0.0, 0.0));
+
+        assert_eq!(blocked.soft_shadow(&settings, &sdf, f64::INFINITY), 0.0);
+        assert_eq!(grazing.soft_shadow(&settings, &sdf, f64::INFINITY), 1.0);
+    }
+
     #[bench]
     fn target_hit(b: &mut Bencher) {
         let ray = Ray::new_unnormalized(Point3::new(-10.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
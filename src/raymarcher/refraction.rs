@@ -0,0 +1,144 @@
+//! Rays that travel through transparent objects.
+//!
+//! A refracted ray is marched inside the object using the negated SDF, which turns the
+//! object's surface into the target of the march. At the exit point the ray is refracted
+//! back out, or reflected back inside on total internal reflection. The distance travelled
+//! inside determines how much light the material absorbs.
+
+use crate::primitives::UnitVec3;
+use crate::raymarcher::{generate_pixel, Ray};
+use crate::scene::scenemap::material::Material;
+use crate::scene::scenemap::sdf::modifiers::Invert;
+use crate::scene::Scene;
+use crate::{Color, Point3, RenderSettings};
+
+/// Color seen through a transparent `material` hit by `ray` at `point`.
+pub(super) fn refraction_contribution(
+    ray: &Ray,
+    point: &Point3,
+    material: &Material,
+    render_settings: &RenderSettings,
+    scene: &Scene,
+    remaining_depth: usize,
+) -> Color {
+    let sdf = scene.scene_map.sdf;
+    let normal = sdf.estimate_normal(point);
+    let ior = material.refractive_index();
+
+    let direction = match ray.direction().as_ref().refract(&normal, 1.0 / ior) {
+        Some(d) => d,
+        None => return Color::BLACK,
+    };
+
+    let offset = surface_offset(render_settings);
+    let mut inner = Ray::new_unnormalized(offset_point(point, &normal, -offset), direction);
+    let inside = Invert::new(sdf);
+    let mut distance_inside = 0.0;
+
+    // Every internal reflection counts as a recursion.
+    for depth in (1..remaining_depth).rev() {
+        let exit = match inner.find_target(&render_settings.find_target_settings, &inside) {
+            Some(exit) => {
+                distance_inside += exit.distance;
+                exit.point
+            }
+            None => return Color::BLACK,
+        };
+        let outward = sdf.estimate_normal(&exit);
+        let inward = -&outward;
+
+        match inner.direction().as_ref().refract(&inward, ior) {
+            Some(d) => {
+                let outer = Ray::new_unnormalized(offset_point(&exit, &outward, offset), d);
+                return generate_pixel(&outer, render_settings, scene, depth)
+                    * &material.transmission(distance_inside);
+            }
+            None => {
+                let d = inner.direction().as_ref().reflect(&inward);
+                inner = Ray::new_unnormalized(offset_point(&exit, &outward, -offset), d);
+            }
+        }
+    }
+
+    Color::BLACK
+}
+
+/// Distance a ray is moved off the surface before marching on, so it doesn't immediately
+/// hit the surface it just crossed.
+fn surface_offset(render_settings: &RenderSettings) -> f64 {
+    10.0 * render_settings.find_target_settings.epsilon
+}
+
+fn offset_point(p: &Point3, normal: &UnitVec3, distance: f64) -> Point3 {
+    Point3(p.as_ref() + normal.as_ref() * distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::{ApproxEq, F64Margin};
+
+    use super::*;
+    use crate::scene::camera::Camera;
+    use crate::scene::scenemap::lights::AmbientLight;
+    use crate::scene::scenemap::material::MaterialList;
+    use crate::scene::scenemap::sdf::primitives::Sphere;
+    use crate::scene::scenemap::SceneMap;
+    use crate::scene::ConstantBackground;
+    use crate::test_constants::MARGIN;
+    use crate::Vec3;
+
+    /// Color seen when looking head-on through a unit sphere made of `material`.
+    fn through_unit_sphere(material: &Material) -> Color {
+        let sdf = Sphere::default();
+        let materials = MaterialList::new();
+        let scene = Scene {
+            camera: Camera::new(
+                Point3::ORIGIN,
+                Point3::new(0.0, 0.0, 5.0),
+                Vec3::new(0.0, 1.0, 0.0),
+                45.0,
+                1.0,
+            ),
+            scene_map: SceneMap {
+                sdf: &sdf,
+                materials: &materials,
+                ambient_light: AmbientLight::new(Color::BLACK),
+                lights: &[],
+            },
+            background: Box::new(ConstantBackground {
+                color: Color::new(0.1, 0.2, 0.3),
+            }),
+        };
+        let settings = RenderSettings::new(0.001, 100.0, 1e-5, 5, None);
+        let ray = Ray::new_unnormalized(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let point = ray
+            .find_target(&settings.find_target_settings, &sdf)
+            .unwrap()
+            .point;
+
+        refraction_contribution(&ray, &point, material, &settings, &scene, 5)
+    }
+
+    #[test]
+    fn head_on_ray_passes_straight_through() {
+        let c = through_unit_sphere(&Material::pure_transparent(1.5));
+
+        assert!(c.0.approx_eq(&Vec3::new(0.1, 0.2, 0.3), MARGIN));
+    }
+
+    #[test]
+    fn absorption_depends_on_thickness() {
+        let absorption = Color::new(0.0, 0.5, 1.0);
+        let c = through_unit_sphere(&Material::pure_transparent(1.5).with_absorption(absorption));
+
+        // The ray spends a distance of 2 inside the sphere
+        let margin = F64Margin {
+            ulps: 0,
+            epsilon: 1e-3,
+        };
+        assert!(c.0.approx_eq(
+            &Vec3::new(0.1, 0.2 * (-1.0_f64).exp(), 0.3 * (-2.0_f64).exp()),
+            margin
+        ));
+    }
+}
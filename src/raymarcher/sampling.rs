@@ -0,0 +1,268 @@
+//! Sample placement within a pixel and the weights used to combine the samples.
+//!
+//! Offsets are in pixel units relative to the pixel position, so they lie in `[-0.5, 0.5)`.
+//! Samples are never shared between pixels, so the filters only weight the samples of a
+//! single pixel rather than reconstructing the image across pixel boundaries.
+
+use crate::Point3;
+
+/// How the samples of a single pixel are distributed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SamplePattern {
+    /// Regular grid. The sample count is rounded up to the next square number.
+    Grid,
+    /// Regular grid rotated by `atan(1/2)`, which avoids aligning samples with horizontal and
+    /// vertical edges. The grid is shrunk to keep the rotated samples inside the pixel. The
+    /// sample count is rounded up to the next square number.
+    RotatedGrid,
+    /// One randomly placed sample per grid cell. The sample count is rounded up to the next
+    /// square number.
+    Stratified,
+    /// Halton sequence in bases 2 and 3, randomly shifted per pixel.
+    Halton,
+}
+
+/// Weighting applied to each sample based on its offset from the pixel position.
+///
+/// These are in-pixel weights, not reconstruction filters: every sample only contributes to
+/// its own pixel, and offsets never exceed half a pixel. Only the center of each kernel is
+/// used, so all of them favour samples near the pixel position to a different degree.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReconstructionFilter {
+    /// All samples have equal weight.
+    Box,
+    /// Weight falls off linearly with the offset, to half at the pixel edges.
+    Tent,
+    /// Gaussian with a standard deviation of half a pixel.
+    Gaussian,
+    /// Central lobe of the Mitchell-Netravali kernel with `B = C = 1/3`. Its negative outer
+    /// lobe lies beyond the pixel and is left out.
+    Mitchell,
+}
+
+impl ReconstructionFilter {
+    /// Weight of a sample at offset `(dx, dy)`, with both in `[-0.5, 0.5]`.
+    pub fn weight(&self, dx: f64, dy: f64) -> f64 {
+        self.weight_1d(dx) * self.weight_1d(dy)
+    }
+
+    fn weight_1d(&self, x: f64) -> f64 {
+        let x = x.abs();
+        match self {
+            ReconstructionFilter::Box => 1.0,
+            ReconstructionFilter::Tent => (1.0 - x).max(0.0),
+            ReconstructionFilter::Gaussian => {
+                let sigma: f64 = 0.5;
+                (-x * x / (2.0 * sigma * sigma)).exp()
+            }
+            ReconstructionFilter::Mitchell => {
+                let b = 1.0 / 3.0;
+                let c = 1.0 / 3.0;
+                let x2 = x * x;
+                let x3 = x2 * x;
+                ((12.0 - 9.0 * b - 6.0 * c) * x3
+                    + (-18.0 + 12.0 * b + 6.0 * c) * x2
+                    + (6.0 - 2.0 * b))
+                    / 6.0
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SampleSettings {
+    pub samples_per_pixel: usize,
+    pub pattern: SamplePattern,
+    pub filter: ReconstructionFilter,
+}
+
+impl SampleSettings {
+    pub fn new(
+        samples_per_pixel: usize,
+        pattern: SamplePattern,
+        filter: ReconstructionFilter,
+    ) -> Self {
+        Self {
+            samples_per_pixel: samples_per_pixel.max(1),
+            pattern,
+            filter,
+        }
+    }
+
+    /// Sample offsets for pixel `(i, j)`.
+    ///
+    /// Random patterns are seeded with the pixel coordinates, so a pixel always gets the same
+    /// samples no matter which thread renders it.
+    pub fn offsets(&self, i: usize, j: usize) -> Vec<(f64, f64)> {
+        let n = self.samples_per_pixel;
+        let side = (n as f64).sqrt().ceil() as usize;
+        let mut rng = Rng::for_pixel(i, j);
+        let cell = |k: usize| ((k % side) as f64 + 0.5, (k / side) as f64 + 0.5);
+
+        match self.pattern {
+            SamplePattern::Grid => (0..side * side)
+                .map(|k| {
+                    let (x, y) = cell(k);
+                    (x / side as f64 - 0.5, y / side as f64 - 0.5)
+                })
+                .collect(),
+            SamplePattern::RotatedGrid => {
+                let angle = 0.5_f64.atan();
+                let (sin, cos) = angle.sin_cos();
+                // A square rotated by `angle` fits in one that is `cos + sin` times as large
+                let scale = 1.0 / (cos + sin);
+                (0..side * side)
+                    .map(|k| {
+                        let (x, y) = cell(k);
+                        let (x, y) = (x / side as f64 - 0.5, y / side as f64 - 0.5);
+                        ((x * cos - y * sin) * scale, (x * sin + y * cos) * scale)
+                    })
+                    .collect()
+            }
+            SamplePattern::Stratified => (0..side * side)
+                .map(|k| {
+                    let (x, y) = cell(k);
+                    let (x, y) = (x - 0.5 + rng.next_f64(), y - 0.5 + rng.next_f64());
+                    (x / side as f64 - 0.5, y / side as f64 - 0.5)
+                })
+                .collect(),
+            SamplePattern::Halton => {
+                let (shift_x, shift_y) = (rng.next_f64(), rng.next_f64());
+                (1..=n)
+                    .map(|k| {
+                        let x = (radical_inverse(k, 2) + shift_x).fract();
+                        let y = (radical_inverse(k, 3) + shift_y).fract();
+                        (x - 0.5, y - 0.5)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+impl Default for SampleSettings {
+    fn default() -> Self {
+        Self::new(1, SamplePattern::Grid, ReconstructionFilter::Box)
+    }
+}
+
+/// `n` points in the unit square for sampling area lights from shading point `p`.
+///
+/// Uses a Halton sequence shifted by a random offset seeded from `p`, so neighbouring
+/// points don't share the same noise pattern but renders stay reproducible.
+pub fn unit_square_samples(p: &Point3, n: usize) -> Vec<(f64, f64)> {
+    let seed = p.0.x.to_bits() ^ p.0.y.to_bits().rotate_left(21) ^ p.0.z.to_bits().rotate_left(42);
+    let mut rng = Rng::new(seed);
+    let (shift_x, shift_y) = (rng.next_f64(), rng.next_f64());
+    (1..=n)
+        .map(|k| {
+            (
+                (radical_inverse(k, 2) + shift_x).fract(),
+                (radical_inverse(k, 3) + shift_y).fract(),
+            )
+        })
+        .collect()
+}
+
+/// Van der Corput radical inverse of `k` in the given base.
+pub fn radical_inverse(mut k: usize, base: usize) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let mut factor = inv_base;
+    let mut result = 0.0;
+    while k > 0 {
+        result += (k % base) as f64 * factor;
+        k /= base;
+        factor *= inv_base;
+    }
+    result
+}
+
+/// Small deterministic pseudo-random generator (SplitMix64).
+///
+/// Rendering must be reproducible regardless of thread scheduling, so every consumer
+/// seeds its own generator instead of sharing one.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn for_pixel(i: usize, j: usize) -> Self {
+        Self::new(((i as u64) << 32) ^ (j as u64))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATTERNS: [SamplePattern; 4] = [
+        SamplePattern::Grid,
+        SamplePattern::RotatedGrid,
+        SamplePattern::Stratified,
+        SamplePattern::Halton,
+    ];
+
+    #[test]
+    fn offsets_stay_inside_pixel() {
+        for pattern in PATTERNS.iter() {
+            let settings = SampleSettings::new(9, *pattern, ReconstructionFilter::Box);
+            let offsets = settings.offsets(3, 7);
+            assert_eq!(offsets.len(), 9);
+            assert!(offsets
+                .iter()
+                .all(|(x, y)| (-0.5..0.5).contains(x) && (-0.5..0.5).contains(y)));
+        }
+    }
+
+    #[test]
+    fn rotated_grid_keeps_rotation() {
+        let settings =
+            SampleSettings::new(9, SamplePattern::RotatedGrid, ReconstructionFilter::Box);
+        let offsets = settings.offsets(0, 0);
+        // Neighbours along a grid row are still separated along the rotated direction
+        for row in offsets.chunks(3) {
+            for pair in row.windows(2) {
+                let (dx, dy) = (pair[1].0 - pair[0].0, pair[1].1 - pair[0].1);
+                assert!((dy / dx - 0.5).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn single_grid_sample_is_pixel_position() {
+        let offsets = SampleSettings::default().offsets(0, 0);
+        assert_eq!(offsets, vec![(0.0, 0.0)]);
+    }
+
+    #[test]
+    fn filters_peak_at_center() {
+        for filter in [
+            ReconstructionFilter::Box,
+            ReconstructionFilter::Tent,
+            ReconstructionFilter::Gaussian,
+            ReconstructionFilter::Mitchell,
+        ]
+        .iter()
+        {
+            let center = filter.weight(0.0, 0.0);
+            assert!(center > 0.0);
+            assert!(filter.weight(0.4, -0.3) <= center);
+        }
+    }
+}
@@ -0,0 +1,56 @@
+/// Rectangular part of the image, in output coordinates (row 0 is the top scanline).
+#[derive(Debug, Clone)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Tile {
+    /// Splits a `width` by `height` image into tiles of at most `size` by `size` pixels,
+    /// in scanline order.
+    pub fn split(width: usize, height: usize, size: usize) -> Vec<Tile> {
+        (0..height)
+            .step_by(size)
+            .flat_map(|y| {
+                (0..width).step_by(size).map(move |x| Tile {
+                    x,
+                    y,
+                    width: size.min(width - x),
+                    height: size.min(height - y),
+                })
+            })
+            .collect()
+    }
+
+    /// Pixel coordinates covered by this tile, row by row.
+    pub fn pixels(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (self.y..self.y + self.height)
+            .flat_map(move |row| (self.x..self.x + self.width).map(move |col| (col, row)))
+    }
+
+    /// Copies the rendered pixels of this tile into a full image buffer of the given width.
+    pub fn blit<T>(&self, pixels: Vec<T>, image: &mut [T], image_width: usize) {
+        for ((col, row), c) in self.pixels().zip(pixels) {
+            image[row * image_width + col] = c;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_cover_image_exactly_once() {
+        let (width, height) = (37, 21);
+        let mut covered = vec![0; width * height];
+        for tile in Tile::split(width, height, 8) {
+            for (col, row) in tile.pixels() {
+                covered[row * width + col] += 1;
+            }
+        }
+        assert!(covered.iter().all(|c| *c == 1));
+    }
+}
@@ -35,7 +35,7 @@ impl Camera {
     }
 
     pub fn get_ray(&self, u: f64, v: f64) -> Ray {
-        let origin = (&self.origin).clone();
+        let origin = self.origin.clone();
         let direction = self.lower_left_corner.as_ref() + &self.horizontal * u + &self.vertical * v
             - self.origin.as_ref();
         Ray::new_unnormalized(origin, direction)
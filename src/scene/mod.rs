@@ -12,7 +12,10 @@ pub struct Scene<'a> {
     pub background: Box<dyn Background>,
 }
 
-pub trait Background {
+/// Color of rays that don't hit anything.
+///
+/// Like [Sdf](crate::scene::scenemap::sdf::Sdf), backgrounds are shared between render threads.
+pub trait Background: Send + Sync {
     fn value_at(&self, r: &Ray) -> Color;
 }
 
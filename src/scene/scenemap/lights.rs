@@ -1,4 +1,10 @@
-use crate::{Color, Point3};
+use std::f64::consts::PI;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::primitives::UnitVec3;
+use crate::scene::scenemap::sdf::Sdf;
+use crate::{Color, Point3, Vec3};
 
 #[derive(Debug, Clone)]
 pub struct AmbientLight(pub Color);
@@ -9,14 +15,560 @@ impl AmbientLight {
     }
 }
 
-// TODO think about attenuation and light types (directional/point/spot)
-// https://blogs.igalia.com/itoral/2017/07/06/working_lights_shadows_parti_phong_reflection_model/
+#[derive(Debug, Clone)]
+pub enum Light {
+    Point(PointLight),
+    Directional(DirectionalLight),
+    Spot(SpotLight),
+    Area(AreaLight),
+}
+
+/// Where a light shines on a point from.
+#[derive(Debug, Clone)]
+pub struct LightSample {
+    /// Direction from the lit point towards the light.
+    pub direction: UnitVec3,
+    /// Distance to the light, infinite for directional lights.
+    pub distance: f64,
+    /// Fraction of the light's strength emitted towards the lit point. Only spot lights
+    /// and flat area lights seen at an angle emit less than all of it.
+    pub intensity: f64,
+}
+
+impl Light {
+    /// Where the light shines on `p` from.
+    ///
+    /// `uv` is a point in the unit square which selects the point on the surface of area
+    /// lights. Other lights ignore it.
+    pub fn sample(&self, p: &Point3, uv: (f64, f64)) -> LightSample {
+        match self {
+            Light::Point(l) => {
+                let v = l.location.as_ref() - p.as_ref();
+                LightSample {
+                    distance: v.length(),
+                    direction: v.unit(),
+                    intensity: 1.0,
+                }
+            }
+            Light::Directional(l) => LightSample {
+                direction: -&l.direction,
+                distance: f64::INFINITY,
+                intensity: 1.0,
+            },
+            Light::Spot(l) => {
+                let v = l.location.as_ref() - p.as_ref();
+                let direction = v.unit();
+                let cos_angle = -direction.as_ref().dot(l.direction.as_ref());
+                LightSample {
+                    distance: v.length(),
+                    direction,
+                    intensity: l.cone_factor(cos_angle),
+                }
+            }
+            Light::Area(l) => l.shape.sample(p, uv),
+        }
+    }
+
+    pub fn specular(&self) -> &Color {
+        match self {
+            Light::Point(l) => &l.specular,
+            Light::Directional(l) => &l.specular,
+            Light::Spot(l) => &l.specular,
+            Light::Area(l) => &l.specular,
+        }
+    }
+
+    pub fn diffuse(&self) -> &Color {
+        match self {
+            Light::Point(l) => &l.diffuse,
+            Light::Directional(l) => &l.diffuse,
+            Light::Spot(l) => &l.diffuse,
+            Light::Area(l) => &l.diffuse,
+        }
+    }
+
+    pub fn strength(&self) -> f64 {
+        match self {
+            Light::Point(l) => l.strength,
+            Light::Directional(l) => l.strength,
+            Light::Spot(l) => l.strength,
+            Light::Area(l) => l.strength,
+        }
+    }
+
+    pub fn attenuation(&self) -> &Attenuation {
+        match self {
+            Light::Point(l) => &l.attenuation,
+            Light::Directional(_) => &Attenuation::None,
+            Light::Spot(l) => &l.attenuation,
+            Light::Area(l) => &l.attenuation,
+        }
+    }
+
+    /// Hardness `k` passed to [Ray::soft_shadow](crate::Ray::soft_shadow).
+    ///
+    /// Area lights get their penumbra from sampling many points on the light, so each of
+    /// their shadow rays is hard.
+    pub fn shadow_hardness(&self) -> f64 {
+        match self {
+            Light::Point(l) => l.shadow_hardness,
+            Light::Directional(l) => l.shadow_hardness(),
+            Light::Spot(l) => l.shadow_hardness,
+            Light::Area(_) => f64::INFINITY,
+        }
+    }
+
+    pub fn is_area(&self) -> bool {
+        matches!(self, Light::Area(_))
+    }
+}
+
+impl From<PointLight> for Light {
+    fn from(l: PointLight) -> Self {
+        Light::Point(l)
+    }
+}
+
+impl From<DirectionalLight> for Light {
+    fn from(l: DirectionalLight) -> Self {
+        Light::Directional(l)
+    }
+}
+
+impl From<SpotLight> for Light {
+    fn from(l: SpotLight) -> Self {
+        Light::Spot(l)
+    }
+}
+
+impl From<AreaLight> for Light {
+    fn from(l: AreaLight) -> Self {
+        Light::Area(l)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PointLight {
+    pub location: Point3,
+    pub specular: Color,
+    pub diffuse: Color,
+    pub strength: f64,
+    pub shadow_hardness: f64,
+    pub attenuation: Attenuation,
+}
 
+/// Light infinitely far away, like the sun.
 #[derive(Debug, Clone)]
-pub struct Light {
+pub struct DirectionalLight {
+    /// Direction the light travels in.
+    pub direction: UnitVec3,
+    pub specular: Color,
+    pub diffuse: Color,
+    pub strength: f64,
+    /// Apparent diameter of the light source in radians, which determines how soft the
+    /// shadows are. The sun is about 0.0093. Zero gives hard shadows.
+    pub angular_size: f64,
+}
+
+impl DirectionalLight {
+    /// A point is in half shadow when the closest occluder along the shadow ray is seen
+    /// within half the angular size of the light.
+    fn shadow_hardness(&self) -> f64 {
+        1.0 / (self.angular_size / 2.0).tan()
+    }
+}
+
+/// Point light that only shines within a cone.
+#[derive(Debug, Clone)]
+pub struct SpotLight {
     pub location: Point3,
+    /// Direction the cone points in.
+    pub direction: UnitVec3,
+    /// Angle in radians between the cone's axis and its edge within which the light has
+    /// full strength.
+    pub inner_angle: f64,
+    /// Angle in radians between the cone's axis and its edge outside of which there is no
+    /// light at all.
+    pub outer_angle: f64,
+    /// How the strength drops between the inner and the outer angle.
+    pub falloff: Falloff,
     pub specular: Color,
     pub diffuse: Color,
     pub strength: f64,
     pub shadow_hardness: f64,
+    pub attenuation: Attenuation,
+}
+
+impl SpotLight {
+    /// Fraction of the strength emitted at an angle with cosine `cos_angle` to the axis.
+    fn cone_factor(&self, cos_angle: f64) -> f64 {
+        let cos_inner = self.inner_angle.cos();
+        let cos_outer = self.outer_angle.cos();
+        if cos_angle >= cos_inner {
+            1.0
+        } else if cos_angle <= cos_outer {
+            0.0
+        } else {
+            self.falloff
+                .apply((cos_angle - cos_outer) / (cos_inner - cos_outer))
+        }
+    }
+}
+
+/// Light emitted from a surface rather than a single point.
+///
+/// Shading points shoot shadow rays to several points on the surface, see
+/// [RenderSettings::with_area_light_samples](crate::RenderSettings::with_area_light_samples),
+/// so the width of the penumbra follows from the size of and the distance to the light.
+#[derive(Debug, Clone)]
+pub struct AreaLight {
+    pub shape: AreaLightShape,
+    pub specular: Color,
+    pub diffuse: Color,
+    pub strength: f64,
+    pub attenuation: Attenuation,
+}
+
+#[derive(Clone)]
+pub enum AreaLightShape {
+    Sphere {
+        center: Point3,
+        radius: f64,
+    },
+    /// Parallelogram spanned by `half_u` and `half_v` in both directions around `center`.
+    /// Only emits on the side `half_u x half_v` points to.
+    Rectangle {
+        center: Point3,
+        half_u: Vec3,
+        half_v: Vec3,
+    },
+    /// Disk with `radius` around `center`, perpendicular to `normal`. Only emits on the side
+    /// `normal` points to.
+    Disk {
+        center: Point3,
+        normal: UnitVec3,
+        radius: f64,
+    },
+    /// Surface of an arbitrary SDF, which must fit in the sphere with `radius` around `center`.
+    /// Samples are found by projecting points on that sphere onto the surface.
+    Sdf {
+        sdf: Arc<dyn Sdf>,
+        center: Point3,
+        radius: f64,
+    },
+}
+
+impl AreaLightShape {
+    /// Newton steps used to project a point onto the surface of an SDF emitter.
+    const SDF_PROJECTION_STEPS: usize = 8;
+
+    fn sample(&self, p: &Point3, (u, v): (f64, f64)) -> LightSample {
+        let (point, normal) = match self {
+            AreaLightShape::Sphere { center, radius } => {
+                // Only the disk facing p is visible
+                let facing = (p.as_ref() - center.as_ref()).unit();
+                let (a, b) = orthonormal_basis(&facing);
+                let (x, y) = concentric_disk(u, v);
+                let offset = a * (x * radius) + b * (y * radius);
+                (Point3(center.as_ref() + offset), None)
+            }
+            AreaLightShape::Rectangle {
+                center,
+                half_u,
+                half_v,
+            } => {
+                let offset = half_u * (2.0 * u - 1.0) + half_v * (2.0 * v - 1.0);
+                (
+                    Point3(center.as_ref() + offset),
+                    Some(half_u.cross(half_v).unit()),
+                )
+            }
+            AreaLightShape::Disk {
+                center,
+                normal,
+                radius,
+            } => {
+                let (a, b) = orthonormal_basis(normal);
+                let (x, y) = concentric_disk(u, v);
+                let offset = a * (x * radius) + b * (y * radius);
+                (Point3(center.as_ref() + offset), Some(normal.clone()))
+            }
+            AreaLightShape::Sdf {
+                sdf,
+                center,
+                radius,
+            } => {
+                let z = 1.0 - 2.0 * u;
+                let r = (1.0 - z * z).max(0.0).sqrt();
+                let phi = 2.0 * PI * v;
+                let direction = Vec3::new(r * phi.cos(), r * phi.sin(), z);
+                let mut q = Point3(center.as_ref() + direction * *radius);
+                for _ in 0..Self::SDF_PROJECTION_STEPS {
+                    let d = sdf.value_at(&q).0;
+                    let n = sdf.estimate_normal(&q);
+                    q = Point3(q.as_ref() - n.as_ref() * d);
+                }
+                (q, None)
+            }
+        };
+
+        let v = point.as_ref() - p.as_ref();
+        let direction = v.unit();
+        // `direction` points towards the light, so the emitting side faces against it
+        let intensity = normal
+            .map(|n| (-n.as_ref().dot(direction.as_ref())).max(0.0))
+            .unwrap_or(1.0);
+        LightSample {
+            distance: v.length(),
+            direction,
+            intensity,
+        }
+    }
+}
+
+impl fmt::Debug for AreaLightShape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AreaLightShape::Sphere { center, radius } => f
+                .debug_struct("Sphere")
+                .field("center", center)
+                .field("radius", radius)
+                .finish(),
+            AreaLightShape::Rectangle {
+                center,
+                half_u,
+                half_v,
+            } => f
+                .debug_struct("Rectangle")
+                .field("center", center)
+                .field("half_u", half_u)
+                .field("half_v", half_v)
+                .finish(),
+            AreaLightShape::Disk {
+                center,
+                normal,
+                radius,
+            } => f
+                .debug_struct("Disk")
+                .field("center", center)
+                .field("normal", normal)
+                .field("radius", radius)
+                .finish(),
+            AreaLightShape::Sdf { center, radius, .. } => f
+                .debug_struct("Sdf")
+                .field("center", center)
+                .field("radius", radius)
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+/// Two unit vectors perpendicular to `n` and each other.
+fn orthonormal_basis(n: &UnitVec3) -> (Vec3, Vec3) {
+    let n = n.as_ref();
+    let helper = if n.x.abs() > 0.9 {
+        Vec3::new(0.0, 1.0, 0.0)
+    } else {
+        Vec3::new(1.0, 0.0, 0.0)
+    };
+    let a = n.cross(&helper).unit().0;
+    let b = n.cross(&a);
+    (a, b)
+}
+
+/// Shirley-Chiu mapping of the unit square onto the unit disk, which keeps stratified
+/// samples evenly spread.
+fn concentric_disk(u: f64, v: f64) -> (f64, f64) {
+    let (a, b) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+    if a == 0.0 && b == 0.0 {
+        return (0.0, 0.0);
+    }
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, PI / 4.0 * (b / a))
+    } else {
+        (b, PI / 2.0 - PI / 4.0 * (a / b))
+    };
+    (r * theta.cos(), r * theta.sin())
+}
+
+/// Maps the position between the outer (0) and inner (1) edge of a spot light's cone to a
+/// strength factor.
+#[derive(Debug, Clone)]
+pub enum Falloff {
+    Linear,
+    Smoothstep,
+    /// Raises the position to the given power. Larger exponents concentrate the light towards
+    /// the inner cone.
+    Power(f64),
+}
+
+impl Falloff {
+    pub fn apply(&self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Falloff::Linear => t,
+            Falloff::Smoothstep => t * t * (3.0 - 2.0 * t),
+            Falloff::Power(e) => t.powf(*e),
+        }
+    }
+}
+
+/// How a light's strength decreases with the distance `d` to the lit point.
+///
+/// Negative coefficients can make a denominator zero or negative; it is then clamped to
+/// [Attenuation::MIN_DENOMINATOR] so the light stays finite and positive.
+// https://blogs.igalia.com/itoral/2017/07/06/working_lights_shadows_parti_phong_reflection_model/
+#[derive(Debug, Clone)]
+pub enum Attenuation {
+    /// Same strength at any distance.
+    None,
+    /// `1 / (1 + k * d)`
+    Linear(f64),
+    /// `1 / (1 + k * d^2)`
+    Quadratic(f64),
+    /// `1 / (constant + linear * d + quadratic * d^2)`
+    ConstantLinearQuadratic {
+        constant: f64,
+        linear: f64,
+        quadratic: f64,
+    },
+    /// Physically based `1 / d^2`, smoothly windowed to reach zero at `range` so far away
+    /// points can skip the light entirely.
+    InverseSquare { range: f64 },
+}
+
+impl Attenuation {
+    /// Distances below this are clamped to avoid blowing up right next to the light.
+    const MIN_DISTANCE: f64 = 0.01;
+
+    /// Smallest denominator of the polynomial variants, which caps them at the strength
+    /// [Attenuation::InverseSquare] reaches at its minimum distance.
+    pub const MIN_DENOMINATOR: f64 = Self::MIN_DISTANCE * Self::MIN_DISTANCE;
+
+    pub fn factor(&self, d: f64) -> f64 {
+        let inverse = |denominator: f64| 1.0 / denominator.max(Self::MIN_DENOMINATOR);
+        match self {
+            Attenuation::None => 1.0,
+            Attenuation::Linear(k) => inverse(1.0 + k * d),
+            Attenuation::Quadratic(k) => inverse(1.0 + k * d * d),
+            Attenuation::ConstantLinearQuadratic {
+                constant,
+                linear,
+                quadratic,
+            } => inverse(constant + linear * d + quadratic * d * d),
+            Attenuation::InverseSquare { range } => {
+                let window = (1.0 - (d / range).powi(4)).clamp(0.0, 1.0).powi(2);
+                let d = d.max(Self::MIN_DISTANCE);
+                window / (d * d)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::ApproxEq;
+
+    use super::*;
+    use crate::test_constants::MARGIN;
+    use crate::Vec3;
+
+    fn spot() -> SpotLight {
+        SpotLight {
+            location: Point3::new(0.0, 10.0, 0.0),
+            direction: Vec3::new(0.0, -1.0, 0.0).unit(),
+            inner_angle: 10.0_f64.to_radians(),
+            outer_angle: 20.0_f64.to_radians(),
+            falloff: Falloff::Smoothstep,
+            specular: Color::WHITE,
+            diffuse: Color::WHITE,
+            strength: 1.0,
+            shadow_hardness: 16.0,
+            attenuation: Attenuation::None,
+        }
+    }
+
+    #[test]
+    fn spot_light_cone() {
+        let light = Light::Spot(spot());
+        let at_angle = |degrees: f64| {
+            let x = 10.0 * degrees.to_radians().tan();
+            light
+                .sample(&Point3::new(x, 0.0, 0.0), (0.5, 0.5))
+                .intensity
+        };
+
+        assert_eq!(at_angle(0.0), 1.0);
+        assert_eq!(at_angle(9.0), 1.0);
+        let halfway = at_angle(15.0);
+        assert!(halfway > 0.0 && halfway < 1.0);
+        assert_eq!(at_angle(21.0), 0.0);
+    }
+
+    #[test]
+    fn flat_area_lights_emit_on_one_side() {
+        // Both face down
+        let shapes = [
+            AreaLightShape::Rectangle {
+                center: Point3::ORIGIN,
+                half_u: Vec3::new(1.0, 0.0, 0.0),
+                half_v: Vec3::new(0.0, 0.0, 1.0),
+            },
+            AreaLightShape::Disk {
+                center: Point3::ORIGIN,
+                normal: Vec3::new(0.0, -1.0, 0.0).unit(),
+                radius: 1.0,
+            },
+        ];
+        for shape in shapes.iter() {
+            let below = shape.sample(&Point3::new(0.0, -5.0, 0.0), (0.5, 0.5));
+            assert!(below.intensity.approx_eq(1.0, MARGIN));
+            let above = shape.sample(&Point3::new(0.0, 5.0, 0.0), (0.5, 0.5));
+            assert_eq!(above.intensity, 0.0);
+        }
+    }
+
+    #[test]
+    fn attenuation_models() {
+        assert_eq!(Attenuation::None.factor(100.0), 1.0);
+        assert_eq!(Attenuation::Linear(0.5).factor(2.0), 0.5);
+        assert_eq!(Attenuation::Quadratic(1.0).factor(3.0), 0.1);
+        let clq = Attenuation::ConstantLinearQuadratic {
+            constant: 1.0,
+            linear: 1.0,
+            quadratic: 2.0,
+        };
+        assert_eq!(clq.factor(1.0), 0.25);
+        let half = Attenuation::ConstantLinearQuadratic {
+            constant: 0.5,
+            linear: 0.0,
+            quadratic: 0.0,
+        };
+        assert_eq!(half.factor(1.0), 2.0);
+
+        let max = 1.0 / Attenuation::MIN_DENOMINATOR;
+        let zero = Attenuation::ConstantLinearQuadratic {
+            constant: 0.0,
+            linear: 0.0,
+            quadratic: 0.0,
+        };
+        assert_eq!(zero.factor(1.0), max);
+        let negative = Attenuation::ConstantLinearQuadratic {
+            constant: -1.0,
+            linear: -1.0,
+            quadratic: -1.0,
+        };
+        assert_eq!(negative.factor(2.0), max);
+        // Zero denominator at d = 2, negative beyond
+        assert_eq!(Attenuation::Linear(-0.5).factor(1.0), 2.0);
+        assert_eq!(Attenuation::Linear(-0.5).factor(2.0), max);
+        assert_eq!(Attenuation::Linear(-0.5).factor(4.0), max);
+        assert_eq!(Attenuation::Quadratic(-0.25).factor(2.0), max);
+        assert_eq!(Attenuation::Quadratic(-0.25).factor(4.0), max);
+
+        let inverse_square = Attenuation::InverseSquare { range: 10.0 };
+        assert!((inverse_square.factor(1.0) - 0.9998).abs() < 1e-6);
+        assert_eq!(inverse_square.factor(10.0), 0.0);
+        assert_eq!(inverse_square.factor(20.0), 0.0);
+    }
 }
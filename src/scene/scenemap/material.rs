@@ -1,17 +1,112 @@
+use std::borrow::Cow;
+
 use crate::scene::scenemap::sdf::Sdf;
 use crate::{Color, Point3, Ray};
 
 /*
 TODO
 
-* Add refraction. Idea: offset target point based on normal (maybe? t_min?), then invert SDF and continue as normal. Is it possible to distinguish inside/outside (for refractive index) then? Unless that's also inverted in inverted SDFs.
 * Allow changing of material based on point and/or normals (for normal shade override)
 
  */
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct MaterialIndex(usize);
 
+/// Weighted mix of materials, as returned by [Sdf::value_at].
+///
+/// Most SDFs consist of a single material, but smooth combinators blend the materials of
+/// both sides near the seam. To stay cheap to copy around a blend holds at most
+/// [MaterialBlend::CAPACITY] materials; mixing in more drops the least important ones.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MaterialBlend {
+    entries: [(MaterialIndex, f64); MaterialBlend::CAPACITY],
+    len: usize,
+}
+
+impl MaterialBlend {
+    pub const CAPACITY: usize = 4;
+
+    pub fn single(m: MaterialIndex) -> Self {
+        Self {
+            entries: [(m, 1.0); Self::CAPACITY],
+            len: 1,
+        }
+    }
+
+    /// Blends `a` and `b`, with `weight_b` between 0 (only `a`) and 1 (only `b`).
+    /// If one side has no material the other one is used as is.
+    pub fn mix(a: Option<Self>, b: Option<Self>, weight_b: f64) -> Option<Self> {
+        let (a, b) = match (a, b) {
+            (Some(a), Some(b)) => (a, b),
+            (a, b) => return a.or(b),
+        };
+        if weight_b <= 0.0 {
+            return Some(a);
+        }
+        if weight_b >= 1.0 {
+            return Some(b);
+        }
+
+        // Both sides hold at most CAPACITY materials, so the merge fits on the stack
+        let mut merged = [(a.entries[0].0, 0.0); 2 * Self::CAPACITY];
+        let mut len = 0;
+        for (m, w) in a.iter() {
+            merged[len] = (m, w * (1.0 - weight_b));
+            len += 1;
+        }
+        for (m, w) in b.iter() {
+            let w = w * weight_b;
+            match merged[..len]
+                .iter_mut()
+                .find(|(existing, _)| *existing == m)
+            {
+                Some(entry) => entry.1 += w,
+                None => {
+                    merged[len] = (m, w);
+                    len += 1;
+                }
+            }
+        }
+
+        // Move the heaviest materials to the front, without sorting the rest
+        let kept = len.min(Self::CAPACITY);
+        for i in 0..kept {
+            let heaviest = (i..len)
+                .max_by(|&x, &y| merged[x].1.total_cmp(&merged[y].1))
+                .unwrap_or(i);
+            merged.swap(i, heaviest);
+        }
+        let total: f64 = merged[..kept].iter().map(|(_, w)| w).sum();
+
+        let mut blend = Self::single(merged[0].0);
+        blend.len = kept;
+        for (entry, (m, w)) in blend.entries.iter_mut().zip(&merged[..kept]) {
+            *entry = (*m, w / total);
+        }
+        Some(blend)
+    }
+
+    /// Materials with their weights, which sum to 1.
+    pub fn iter(&self) -> impl Iterator<Item = (MaterialIndex, f64)> + Clone + '_ {
+        self.entries[..self.len].iter().copied()
+    }
+
+    /// The material with the largest weight.
+    pub fn dominant(&self) -> MaterialIndex {
+        self.iter()
+            .max_by(|(_, x), (_, y)| x.total_cmp(y))
+            .map(|(m, _)| m)
+            .unwrap_or(self.entries[0].0)
+    }
+}
+
+impl From<MaterialIndex> for MaterialBlend {
+    fn from(m: MaterialIndex) -> Self {
+        Self::single(m)
+    }
+}
+
 #[derive(Default)]
 pub struct MaterialList {
     mats: Vec<Material>,
@@ -30,14 +125,108 @@ impl MaterialList {
     pub fn get(&self, idx: MaterialIndex) -> Option<&Material> {
         self.mats.get(idx.0)
     }
+
+    /// Looks up the materials in `blend` and interpolates their properties.
+    ///
+    /// Unknown indices are ignored; returns [None] if none of them are known.
+    pub fn resolve(&self, blend: &MaterialBlend) -> Option<Cow<'_, Material>> {
+        let parts = blend
+            .iter()
+            .filter_map(move |(m, w)| self.get(m).map(|mat| (mat, w)));
+        let mut known = parts.clone();
+        match (known.next(), known.next()) {
+            (None, _) => None,
+            (Some((m, _)), None) => Some(Cow::Borrowed(m)),
+            _ => Some(Cow::Owned(Material::blend(parts))),
+        }
+    }
+}
+
+/// How much light a surface reflects depending on the angle it is seen at.
+#[derive(Debug, Clone)]
+pub enum Fresnel {
+    /// Reflects [Material::reflectivity] regardless of angle.
+    None,
+    /// Schlick's approximation. Transparent materials derive the reflectance at normal
+    /// incidence from their refractive index, opaque materials use their reflectivity.
+    Schlick,
+    /// Exact Fresnel equations for unpolarized light hitting a dielectric with the material's
+    /// refractive index.
+    Dielectric,
+    /// Exact Fresnel equations for a conductor with complex refractive index `eta + i k`,
+    /// given per color channel. Gold is roughly `eta = (0.18, 0.42, 1.37)`,
+    /// `k = (3.42, 2.35, 1.77)`.
+    Conductor { eta: Color, k: Color },
 }
 
+impl Fresnel {
+    /// Fraction of light reflected by `material` when hit at an angle with cosine `cos_i`
+    /// from outside.
+    pub fn reflectance(&self, material: &Material, cos_i: f64) -> Color {
+        let cos_i = cos_i.clamp(0.0, 1.0);
+        match self {
+            Fresnel::None => Color::grey(material.reflectivity()),
+            Fresnel::Schlick => {
+                let f0 = if material.transparency() > 0.0 {
+                    let n = material.refractive_index();
+                    ((n - 1.0) / (n + 1.0)).powi(2)
+                } else {
+                    material.reflectivity()
+                };
+                Color::grey(f0 + (1.0 - f0) * (1.0 - cos_i).powi(5))
+            }
+            Fresnel::Dielectric => Color::grey(dielectric(cos_i, material.refractive_index())),
+            Fresnel::Conductor { eta, k } => Color::new(
+                conductor(cos_i, eta.r(), k.r()),
+                conductor(cos_i, eta.g(), k.g()),
+                conductor(cos_i, eta.b(), k.b()),
+            ),
+        }
+    }
+}
+
+fn dielectric(cos_i: f64, eta: f64) -> f64 {
+    let sin_t2 = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin_t2 >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin_t2).sqrt();
+    let rs = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    let rp = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    (rs * rs + rp * rp) / 2.0
+}
+
+fn conductor(cos_i: f64, eta: f64, k: f64) -> f64 {
+    let cos2 = cos_i * cos_i;
+    let sin2 = 1.0 - cos2;
+    let eta2 = eta * eta;
+    let k2 = k * k;
+
+    let t0 = eta2 - k2 - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+    let t1 = a2_plus_b2 + cos2;
+    let a = (0.5 * (a2_plus_b2 + t0)).sqrt();
+    let t2 = 2.0 * cos_i * a;
+    let rs = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+
+    (rs + rp) / 2.0
+}
+
+#[derive(Debug, Clone)]
 pub struct Material {
     pub specular: Color,
     pub diffuse: Color,
     pub ambient: Color,
     pub shininess: f64,
     pub reflectivity: f64,
+    pub transparency: f64,
+    pub refractive_index: f64,
+    pub fresnel: Fresnel,
+    pub absorption: Color,
 }
 
 impl Material {
@@ -56,6 +245,45 @@ impl Material {
             ambient,
             shininess,
             reflectivity,
+            transparency: 0.0,
+            refractive_index: 1.0,
+            fresnel: Fresnel::None,
+            absorption: Color::BLACK,
+        }
+    }
+
+    /// Weighted average of the properties of `parts`. Fresnel terms can't be averaged, so
+    /// the one of the heaviest material is used.
+    ///
+    /// `parts` is walked once per property, so it should be cheap to clone.
+    pub fn blend<'a, I>(parts: I) -> Self
+    where
+        I: Iterator<Item = (&'a Material, f64)> + Clone,
+    {
+        let total: f64 = parts.clone().map(|(_, w)| w).sum();
+        let color = |f: fn(&Material) -> &Color| {
+            parts
+                .clone()
+                .fold(Color::BLACK, |acc, (m, w)| acc + f(m) * (w / total))
+        };
+        let scalar =
+            |f: fn(&Material) -> f64| parts.clone().map(|(m, w)| f(m) * (w / total)).sum::<f64>();
+        let fresnel = parts
+            .clone()
+            .max_by(|(_, x), (_, y)| x.total_cmp(y))
+            .map(|(m, _)| m.fresnel.clone())
+            .unwrap_or(Fresnel::None);
+
+        Self {
+            specular: color(|m| &m.specular),
+            diffuse: color(|m| &m.diffuse),
+            ambient: color(|m| &m.ambient),
+            shininess: scalar(|m| m.shininess),
+            reflectivity: scalar(|m| m.reflectivity),
+            transparency: scalar(|m| m.transparency),
+            refractive_index: scalar(|m| m.refractive_index),
+            fresnel,
+            absorption: color(|m| &m.absorption),
         }
     }
 
@@ -63,6 +291,34 @@ impl Material {
         Self::new(Color::BLACK, Color::BLACK, Color::BLACK, 0.0, 1.0)
     }
 
+    /// Clear material that only refracts, like glass (1.5) or water (1.33).
+    pub const fn pure_transparent(refractive_index: f64) -> Self {
+        Self::new(Color::BLACK, Color::BLACK, Color::BLACK, 0.0, 0.0)
+            .with_refraction(1.0, refractive_index)
+    }
+
+    /// Lets `transparency` of the light through the surface, bending it according to
+    /// `refractive_index` (relative to the surrounding air).
+    pub const fn with_refraction(self, transparency: f64, refractive_index: f64) -> Self {
+        Self {
+            transparency,
+            refractive_index,
+            ..self
+        }
+    }
+
+    /// Makes the balance between reflection, refraction and Phong shading depend on the
+    /// viewing angle.
+    pub const fn with_fresnel(self, fresnel: Fresnel) -> Self {
+        Self { fresnel, ..self }
+    }
+
+    /// Tints light travelling through the inside of a transparent material following the
+    /// Beer-Lambert law: each color channel is attenuated by `exp(-absorption * distance)`.
+    pub const fn with_absorption(self, absorption: Color) -> Self {
+        Self { absorption, ..self }
+    }
+
     pub fn specular(&self) -> Color {
         self.specular.clone()
     }
@@ -80,6 +336,37 @@ impl Material {
         self.reflectivity
     }
 
+    pub fn transparency(&self) -> f64 {
+        self.transparency
+    }
+
+    pub fn refractive_index(&self) -> f64 {
+        self.refractive_index
+    }
+
+    pub fn fresnel(&self) -> &Fresnel {
+        &self.fresnel
+    }
+
+    pub fn absorption(&self) -> Color {
+        self.absorption.clone()
+    }
+
+    /// Fraction of light per color channel that survives travelling `distance` through
+    /// the material.
+    pub fn transmission(&self, distance: f64) -> Color {
+        Color::new(
+            (-self.absorption.r() * distance).exp(),
+            (-self.absorption.g() * distance).exp(),
+            (-self.absorption.b() * distance).exp(),
+        )
+    }
+
+    /// Fraction of light reflected when hit at an angle with cosine `cos_i`.
+    pub fn reflectance(&self, cos_i: f64) -> Color {
+        self.fresnel.reflectance(self, cos_i)
+    }
+
     pub fn child_ray(&self, sdf: &dyn Sdf, p: &Point3, incoming: &Ray) -> Option<Ray> {
         let normal = sdf.estimate_normal(p);
         let reflected_direction = incoming.direction().as_ref().reflect(&normal);
@@ -92,3 +379,126 @@ impl Default for Material {
         Self::DEFAULT
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::ApproxEq;
+
+    use super::*;
+    use crate::test_constants::MARGIN;
+
+    #[test]
+    fn blend_merges_and_normalizes() {
+        let mut list = MaterialList::new();
+        let red = list.insert(Material::new(
+            Color::new(1.0, 0.0, 0.0),
+            Color::new(1.0, 0.0, 0.0),
+            Color::BLACK,
+            10.0,
+            0.0,
+        ));
+        let blue = list.insert(Material::new(
+            Color::new(0.0, 0.0, 1.0),
+            Color::new(0.0, 0.0, 1.0),
+            Color::BLACK,
+            30.0,
+            1.0,
+        ));
+
+        let blend = MaterialBlend::mix(Some(red.into()), Some(blue.into()), 0.25).unwrap();
+        assert_eq!(blend.dominant(), red);
+        let again = MaterialBlend::mix(Some(blend), Some(red.into()), 0.5).unwrap();
+        assert_eq!(again.iter().count(), 2);
+        assert!(again
+            .iter()
+            .map(|(_, w)| w)
+            .sum::<f64>()
+            .approx_eq(1.0, MARGIN));
+
+        let mat = list.resolve(&blend).unwrap();
+        assert!(mat.diffuse().r().approx_eq(0.75, MARGIN));
+        assert!(mat.diffuse().b().approx_eq(0.25, MARGIN));
+        assert!(mat.shininess().approx_eq(15.0, MARGIN));
+        assert!(mat.reflectivity().approx_eq(0.25, MARGIN));
+    }
+
+    #[test]
+    fn mixing_full_blends_keeps_heaviest() {
+        let mut list = MaterialList::new();
+        let mats: Vec<MaterialIndex> = (0..8).map(|_| list.insert(Material::default())).collect();
+        let full = |ms: &[MaterialIndex]| {
+            ms[1..]
+                .iter()
+                .enumerate()
+                .fold(MaterialBlend::from(ms[0]), |acc, (i, m)| {
+                    MaterialBlend::mix(Some(acc), Some((*m).into()), 1.0 / (i + 2) as f64).unwrap()
+                })
+        };
+        // Equal weights within each side, so the heavier side wins as a whole
+        let blend =
+            MaterialBlend::mix(Some(full(&mats[..4])), Some(full(&mats[4..])), 0.6).unwrap();
+        let mut kept: Vec<MaterialIndex> = blend.iter().map(|(m, _)| m).collect();
+        kept.sort_by_key(|m| m.0);
+        assert_eq!(kept, mats[4..].to_vec());
+        assert!(blend.iter().all(|(_, w)| w.approx_eq(0.25, MARGIN)));
+    }
+
+    #[test]
+    fn blend_keeps_most_important_materials() {
+        let mut list = MaterialList::new();
+        let mats: Vec<MaterialIndex> = (0..6).map(|_| list.insert(Material::default())).collect();
+        let blend = mats
+            .iter()
+            .skip(1)
+            .try_fold(MaterialBlend::from(mats[0]), |acc, m| {
+                MaterialBlend::mix(Some(acc), Some((*m).into()), 0.3)
+            });
+        let blend = blend.unwrap();
+        assert_eq!(blend.iter().count(), MaterialBlend::CAPACITY);
+        // The first material decays slowest, so the second and third are dropped
+        assert!(!blend.iter().any(|(m, _)| m == mats[1] || m == mats[2]));
+        assert!(blend
+            .iter()
+            .map(|(_, w)| w)
+            .sum::<f64>()
+            .approx_eq(1.0, MARGIN));
+    }
+
+    #[test]
+    fn transmission_decays_exponentially() {
+        let m = Material::pure_transparent(1.5).with_absorption(Color::new(0.0, 1.0, 2.0));
+        let t = m.transmission(0.5);
+        assert!(t.r().approx_eq(1.0, MARGIN));
+        assert!(t.g().approx_eq((-0.5_f64).exp(), MARGIN));
+        assert!(t.b().approx_eq((-1.0_f64).exp(), MARGIN));
+    }
+
+    #[test]
+    fn schlick_matches_normal_incidence_and_grazing() {
+        let m = Material::pure_transparent(1.5).with_fresnel(Fresnel::Schlick);
+        assert!(m.reflectance(1.0).r().approx_eq(0.04, MARGIN));
+        assert!(m.reflectance(0.0).r().approx_eq(1.0, MARGIN));
+    }
+
+    #[test]
+    fn dielectric_at_normal_incidence() {
+        let m = Material::pure_transparent(1.5).with_fresnel(Fresnel::Dielectric);
+        assert!(m.reflectance(1.0).r().approx_eq(0.04, MARGIN));
+        assert!(m.reflectance(0.5).r() > 0.04);
+    }
+
+    #[test]
+    fn conductor_without_absorption_is_dielectric() {
+        let eta = 1.7;
+        let m = Material::pure_transparent(eta).with_fresnel(Fresnel::Conductor {
+            eta: Color::grey(eta),
+            k: Color::BLACK,
+        });
+        for cos_i in [0.1, 0.5, 0.9].iter() {
+            assert!(m
+                .reflectance(*cos_i)
+                .g()
+                .approx_eq(dielectric(*cos_i, eta), MARGIN));
+        }
+    }
+}
@@ -0,0 +1,281 @@
+//! Bounding volume hierarchy over many SDFs.
+//!
+//! Like [MultiUnion], a [Bvh] is the union of its children, each of which comes with a
+//! bounding box. The boxes are organised in a binary tree, so evaluating the union only
+//! visits the branches near `p` and the cost grows roughly logarithmically with the number
+//! of children.
+
+use crate::scene::scenemap::material::MaterialBlend;
+use crate::scene::scenemap::sdf::combinators::MultiUnion;
+use crate::scene::scenemap::sdf::{bounded, Sdf};
+use crate::{Aabb, Point3, Vec3};
+
+/// Maximum number of children in a leaf.
+const LEAF_SIZE: usize = 4;
+
+/// Deepest possible tree: every split halves the children, so this is plenty.
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug)]
+enum Node {
+    /// Children `start..end`.
+    Leaf {
+        bounds: Aabb,
+        start: usize,
+        end: usize,
+    },
+    Inner {
+        bounds: Aabb,
+        left: usize,
+        right: usize,
+    },
+}
+
+impl Node {
+    fn bounds(&self) -> &Aabb {
+        match self {
+            Node::Leaf { bounds, .. } | Node::Inner { bounds, .. } => bounds,
+        }
+    }
+}
+
+pub struct Bvh<'a> {
+    nodes: Vec<Node>,
+    bounds: Vec<Aabb>,
+    children: Vec<Box<dyn Sdf + 'a>>,
+    /// Children with infinite bounds, which are always evaluated.
+    unbounded: Vec<(Aabb, Box<dyn Sdf + 'a>)>,
+}
+
+impl<'a> Bvh<'a> {
+    /// Builds the hierarchy. Every child must lie completely inside its box.
+    pub fn new(children: Vec<(Aabb, Box<dyn Sdf + 'a>)>) -> Self {
+        let (mut bounded, unbounded): (Vec<_>, Vec<_>) = children
+            .into_iter()
+            .partition(|(bounds, _)| bounds.is_finite());
+
+        let mut nodes = Vec::new();
+        if !bounded.is_empty() {
+            let len = bounded.len();
+            build(&mut nodes, &mut bounded, 0, len);
+        }
+        let (bounds, children) = bounded.into_iter().unzip();
+
+        Self {
+            nodes,
+            bounds,
+            children,
+            unbounded,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.children.len() + self.unbounded.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<'a> From<MultiUnion<'a>> for Bvh<'a> {
+    fn from(union: MultiUnion<'a>) -> Self {
+        Self::new(union.children)
+    }
+}
+
+/// Builds the subtree over `children[start..end]`, reordering them so every leaf covers a
+/// contiguous range, and returns the index of its root.
+fn build(
+    nodes: &mut Vec<Node>,
+    children: &mut [(Aabb, Box<dyn Sdf + '_>)],
+    start: usize,
+    end: usize,
+) -> usize {
+    let bounds = children[start..end]
+        .iter()
+        .skip(1)
+        .fold(children[start].0.clone(), |acc, (b, _)| acc.union(b));
+
+    if end - start <= LEAF_SIZE {
+        nodes.push(Node::Leaf { bounds, start, end });
+        return nodes.len() - 1;
+    }
+
+    // Median split along the axis in which the centers are spread out the most
+    let first = children[start].0.center();
+    let centers =
+        children[start..end]
+            .iter()
+            .fold(Aabb::new(first.clone(), first), |acc, (b, _)| {
+                let c = b.center();
+                acc.union(&Aabb::new(c.clone(), c))
+            });
+    let Vec3 { x, y, z } = centers.size();
+    let axis = |p: &Point3| {
+        if x >= y && x >= z {
+            p.0.x
+        } else if y >= z {
+            p.0.y
+        } else {
+            p.0.z
+        }
+    };
+    let mid = start + (end - start) / 2;
+    children[start..end].select_nth_unstable_by(mid - start, |(a, _), (b, _)| {
+        axis(&a.center()).total_cmp(&axis(&b.center()))
+    });
+
+    // Reserve the slot for this node, it's filled in once the children are known
+    let index = nodes.len();
+    nodes.push(Node::Leaf {
+        bounds: bounds.clone(),
+        start,
+        end,
+    });
+    let left = build(nodes, children, start, mid);
+    let right = build(nodes, children, mid, end);
+    nodes[index] = Node::Inner {
+        bounds,
+        left,
+        right,
+    };
+    index
+}
+
+impl<'a> Sdf for Bvh<'a> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let mut best = (f64::INFINITY, None);
+        for (_, sdf) in self.unbounded.iter() {
+            let d = sdf.value_at(p);
+            if d.0 < best.0 {
+                best = d;
+            }
+        }
+        if self.nodes.is_empty() {
+            return best;
+        }
+
+        // Depth-first, visiting the nearer child first so `best` shrinks quickly and more
+        // of the remaining branches can be culled.
+        let mut stack = [0; MAX_DEPTH];
+        let mut len = 1;
+        while len > 0 {
+            len -= 1;
+            let node = &self.nodes[stack[len]];
+            if node.bounds().can_cull(p, best.0) {
+                continue;
+            }
+            match node {
+                Node::Leaf { start, end, .. } => {
+                    for i in *start..*end {
+                        if self.bounds[i].can_cull(p, best.0) {
+                            continue;
+                        }
+                        let d = self.children[i].value_at(p);
+                        if d.0 < best.0 {
+                            best = d;
+                        }
+                    }
+                }
+                Node::Inner { left, right, .. } => {
+                    let dl = self.nodes[*left].bounds().distance_squared_to(p);
+                    let dr = self.nodes[*right].bounds().distance_squared_to(p);
+                    let (near, far) = if dl <= dr {
+                        (*left, *right)
+                    } else {
+                        (*right, *left)
+                    };
+                    stack[len] = far;
+                    stack[len + 1] = near;
+                    len += 2;
+                }
+            }
+        }
+        best
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.nodes
+            .first()
+            .map(Node::bounds)
+            .into_iter()
+            .chain(self.unbounded.iter().map(|(b, _)| b))
+            .cloned()
+            .reduce(|acc, b| acc.union(&b))
+            .and_then(bounded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate test;
+
+    use test::black_box;
+    use test::Bencher;
+
+    use float_cmp::ApproxEq;
+
+    use super::*;
+    use crate::scene::scenemap::sdf::primitives::{NegY, Sphere};
+    use crate::test_constants::MARGIN;
+
+    /// `n` small spheres scattered pseudo-randomly over a 100 by 100 area.
+    fn scatter(n: usize) -> MultiUnion<'static> {
+        (0..n).fold(MultiUnion::new(), |acc, k| {
+            let k = k as f64;
+            let center = Point3::new(
+                (k * 12.9898).sin().abs() * 100.0,
+                (k * 4.1414).sin(),
+                (k * 78.233).sin().abs() * 100.0,
+            );
+            let radius = 0.2 + 0.3 * (k * 3.7).sin().abs();
+            let bounds = Aabb::from_center(&center, &Vec3::new(radius, radius, radius));
+            acc.with(bounds, Sphere::new(radius, center))
+        })
+    }
+
+    #[test]
+    fn bvh_matches_flat_union() {
+        let flat = scatter(500);
+        let bvh = Bvh::from(scatter(500));
+        assert_eq!(bvh.len(), 500);
+
+        for k in 0..200 {
+            let k = k as f64;
+            let p = Point3::new(
+                (k * 0.37).sin() * 60.0 + 50.0,
+                (k * 1.3).cos() * 2.0,
+                (k * 0.91).cos() * 60.0 + 50.0,
+            );
+            assert!(bvh.value_at(&p).0.approx_eq(flat.value_at(&p).0, MARGIN));
+        }
+    }
+
+    #[test]
+    fn unbounded_children_are_always_evaluated() {
+        let bvh = Bvh::from(scatter(20).with(Aabb::INFINITE, NegY));
+        assert!(bvh
+            .value_at(&Point3::new(-500.0, 3.0, -500.0))
+            .0
+            .approx_eq(3.0, MARGIN));
+        assert!(Bvh::new(Vec::new())
+            .value_at(&Point3::ORIGIN)
+            .0
+            .is_infinite());
+    }
+
+    #[bench]
+    fn bvh_4000_spheres(b: &mut Bencher) {
+        let sdf = Bvh::from(scatter(4000));
+        let p = Point3::new(42.0, 0.5, 57.0);
+        b.iter(|| black_box(&sdf).value_at(black_box(&p)))
+    }
+
+    #[bench]
+    fn multi_union_4000_spheres(b: &mut Bencher) {
+        let sdf = scatter(4000);
+        let p = Point3::new(42.0, 0.5, 57.0);
+        b.iter(|| black_box(&sdf).value_at(black_box(&p)))
+    }
+}
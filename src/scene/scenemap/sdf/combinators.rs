@@ -1,11 +1,13 @@
 //! Contains structs that can combine two SDFs into one.
 //!
-//! Implementations are encouraged to propagate any [MaterialIndex] whenever possible,
+//! Implementations are encouraged to propagate any [MaterialBlend] whenever possible,
 //! but are not required to do so.
 
-use crate::scene::scenemap::material::MaterialIndex;
-use crate::scene::scenemap::sdf::Sdf;
-use crate::Point3;
+use std::f64::consts::{FRAC_1_SQRT_2, SQRT_2};
+
+use crate::scene::scenemap::material::MaterialBlend;
+use crate::scene::scenemap::sdf::{bounded, Sdf};
+use crate::{Aabb, Point3};
 
 #[derive(Debug, Clone)]
 pub struct Union<A, B> {
@@ -20,7 +22,7 @@ impl<A, B> Union<A, B> {
 }
 
 impl<A: Sdf, B: Sdf> Sdf for Union<A, B> {
-    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialIndex>) {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
         let da = self.a.value_at(p);
         let db = self.b.value_at(p);
         if da.0 < db.0 {
@@ -29,6 +31,10 @@ impl<A: Sdf, B: Sdf> Sdf for Union<A, B> {
             db
         }
     }
+
+    fn bounds(&self) -> Option<Aabb> {
+        union_bounds(self.a.bounds(), self.b.bounds())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -44,7 +50,7 @@ impl<A, B> Intersect<A, B> {
 }
 
 impl<A: Sdf, B: Sdf> Sdf for Intersect<A, B> {
-    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialIndex>) {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
         let da = self.a.value_at(p);
         let db = self.b.value_at(p);
         if da.0 > db.0 {
@@ -53,6 +59,10 @@ impl<A: Sdf, B: Sdf> Sdf for Intersect<A, B> {
             db
         }
     }
+
+    fn bounds(&self) -> Option<Aabb> {
+        intersect_bounds(self.a.bounds(), self.b.bounds())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -68,7 +78,7 @@ impl<A, B> Difference<A, B> {
 }
 
 impl<A: Sdf, B: Sdf> Sdf for Difference<A, B> {
-    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialIndex>) {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
         let da = self.a.value_at(p);
         let db = self.b.value_at(p);
         if da.0 > -db.0 {
@@ -77,4 +87,869 @@ impl<A: Sdf, B: Sdf> Sdf for Difference<A, B> {
             (-db.0, db.1)
         }
     }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.a.bounds()
+    }
+}
+
+/// Union of any number of SDFs.
+///
+/// Every child comes with a bounding box that must contain the whole child. Children are
+/// checked in the order they were added, and those whose box is farther away than the
+/// closest surface found so far are skipped. How many get skipped therefore depends on
+/// that order; for large scenes build a [super::bvh::Bvh] instead, which doesn't.
+#[derive(Default)]
+pub struct MultiUnion<'a> {
+    pub(super) children: Vec<(Aabb, Box<dyn Sdf + 'a>)>,
+}
+
+impl<'a> MultiUnion<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `sdf`, which must lie completely inside `bounds`.
+    /// Use [Aabb::INFINITE] for children that are not bounded.
+    ///
+    /// Children are checked in the order they are added, so adding large or central ones
+    /// first gives a close surface early and lets more of the later ones be skipped.
+    pub fn push<A: Sdf + 'a>(&mut self, bounds: Aabb, sdf: A) {
+        self.children.push((bounds, Box::new(sdf)));
+    }
+
+    /// Adds `sdf` using its own [Sdf::bounds]. The same ordering advice as for
+    /// [Self::push] applies.
+    pub fn add<A: Sdf + 'a>(&mut self, sdf: A) {
+        let bounds = sdf.bounds().unwrap_or(Aabb::INFINITE);
+        self.push(bounds, sdf);
+    }
+
+    pub fn with<A: Sdf + 'a>(mut self, bounds: Aabb, sdf: A) -> Self {
+        self.push(bounds, sdf);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.children.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.children.is_empty()
+    }
+}
+
+impl<'a> Sdf for MultiUnion<'a> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        // A single pass computes every box distance once; the bound tightens as it goes
+        let mut best = (f64::INFINITY, None);
+        for (bounds, sdf) in self.children.iter() {
+            if bounds.can_cull(p, best.0) {
+                continue;
+            }
+            let d = sdf.value_at(p);
+            if d.0 < best.0 {
+                best = d;
+            }
+        }
+        best
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.children
+            .iter()
+            .map(|(b, _)| b.clone())
+            .reduce(|acc, b| acc.union(&b))
+            .and_then(bounded)
+    }
+}
+
+/// Bounds of the union of two SDFs, unbounded if either one is, or if two half spaces
+/// together cover all sides.
+fn union_bounds(a: Option<Aabb>, b: Option<Aabb>) -> Option<Aabb> {
+    bounded(a?.union(&b?))
+}
+
+/// Bounds of the intersection of two SDFs, unbounded only if both are.
+fn intersect_bounds(a: Option<Aabb>, b: Option<Aabb>) -> Option<Aabb> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.intersection(&b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Material of `b` if `use_b` holds, otherwise that of `a`, falling back to the other side
+/// if the preferred one has none.
+fn pick(
+    use_b: bool,
+    ma: Option<MaterialBlend>,
+    mb: Option<MaterialBlend>,
+) -> Option<MaterialBlend> {
+    if use_b {
+        mb.or(ma)
+    } else {
+        ma.or(mb)
+    }
+}
+
+// The operators below are taken from hg_sdf, see https://mercury.sexy/hg_sdf/
+
+fn chamfer_union(a: f64, b: f64, r: f64) -> f64 {
+    a.min(b).min((a - r + b) * FRAC_1_SQRT_2)
+}
+
+fn chamfer_intersect(a: f64, b: f64, r: f64) -> f64 {
+    a.max(b).max((a + r + b) * FRAC_1_SQRT_2)
+}
+
+fn stairs_union(a: f64, b: f64, r: f64, steps: usize) -> f64 {
+    let s = r / steps as f64;
+    let u = b - r;
+    a.min(b)
+        .min(0.5 * (u + a + ((u - a + s).rem_euclid(2.0 * s) - s).abs()))
+}
+
+fn column_radius(r: f64, columns: usize) -> f64 {
+    r * SQRT_2 / ((columns - 1) as f64 * 2.0 + SQRT_2)
+}
+
+fn columns_union(a: f64, b: f64, r: f64, columns: usize) -> f64 {
+    if a >= r || b >= r {
+        return a.min(b);
+    }
+    let cr = column_radius(r, columns);
+    // Rotate by 45 degrees so the crease runs along y
+    let mut x = (a + b) * FRAC_1_SQRT_2;
+    let mut y = (b - a) * FRAC_1_SQRT_2;
+    x += cr * SQRT_2 - FRAC_1_SQRT_2 * r;
+    if columns % 2 == 1 {
+        y += cr;
+    }
+    y = (y + cr).rem_euclid(2.0 * cr) - cr;
+    ((x * x + y * y).sqrt() - cr).min(x).min(a).min(b)
+}
+
+fn columns_difference(a: f64, b: f64, r: f64, columns: usize) -> f64 {
+    let a = -a;
+    if a >= r || b >= r {
+        return -a.min(b);
+    }
+    let cr = column_radius(r, columns);
+    let mut x = (a + b) * FRAC_1_SQRT_2;
+    let mut y = (b - a) * FRAC_1_SQRT_2 + cr;
+    x += -FRAC_1_SQRT_2 * r - cr * FRAC_1_SQRT_2;
+    if columns % 2 == 1 {
+        y += cr;
+    }
+    y = (y + cr).rem_euclid(2.0 * cr) - cr;
+    -(cr - (x * x + y * y).sqrt()).max(x).min(a).min(b)
+}
+
+/// Union with a 45 degree chamfer of size `radius` along the crease.
+#[derive(Debug, Clone)]
+pub struct ChamferUnion<A, B> {
+    a: A,
+    b: B,
+    radius: f64,
+}
+
+impl<A, B> ChamferUnion<A, B> {
+    pub fn new(a: A, b: B, radius: f64) -> Self {
+        Self { a, b, radius }
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for ChamferUnion<A, B> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let (da, ma) = self.a.value_at(p);
+        let (db, mb) = self.b.value_at(p);
+        (chamfer_union(da, db, self.radius), pick(db < da, ma, mb))
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        union_bounds(self.a.bounds(), self.b.bounds()).map(|b| b.expand(self.radius))
+    }
+}
+
+/// Intersection with a 45 degree chamfer of size `radius` along the edge.
+#[derive(Debug, Clone)]
+pub struct ChamferIntersect<A, B> {
+    a: A,
+    b: B,
+    radius: f64,
+}
+
+impl<A, B> ChamferIntersect<A, B> {
+    pub fn new(a: A, b: B, radius: f64) -> Self {
+        Self { a, b, radius }
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for ChamferIntersect<A, B> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let (da, ma) = self.a.value_at(p);
+        let (db, mb) = self.b.value_at(p);
+        (
+            chamfer_intersect(da, db, self.radius),
+            pick(db > da, ma, mb),
+        )
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        intersect_bounds(self.a.bounds(), self.b.bounds())
+    }
+}
+
+/// Difference with a 45 degree chamfer of size `radius` along the cut.
+#[derive(Debug, Clone)]
+pub struct ChamferDifference<A, B> {
+    a: A,
+    b: B,
+    radius: f64,
+}
+
+impl<A, B> ChamferDifference<A, B> {
+    pub fn new(a: A, b: B, radius: f64) -> Self {
+        Self { a, b, radius }
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for ChamferDifference<A, B> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let (da, ma) = self.a.value_at(p);
+        let (db, mb) = self.b.value_at(p);
+        (
+            chamfer_intersect(da, -db, self.radius),
+            pick(-db > da, ma, mb),
+        )
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.a.bounds()
+    }
+}
+
+/// Union that fills the crease with `steps` stairs spanning `radius`.
+#[derive(Debug, Clone)]
+pub struct StairsUnion<A, B> {
+    a: A,
+    b: B,
+    radius: f64,
+    steps: usize,
+}
+
+impl<A, B> StairsUnion<A, B> {
+    pub fn new(a: A, b: B, radius: f64, steps: usize) -> Self {
+        Self {
+            a,
+            b,
+            radius,
+            steps: steps.max(1),
+        }
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for StairsUnion<A, B> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let (da, ma) = self.a.value_at(p);
+        let (db, mb) = self.b.value_at(p);
+        (
+            stairs_union(da, db, self.radius, self.steps),
+            pick(db < da, ma, mb),
+        )
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        union_bounds(self.a.bounds(), self.b.bounds()).map(|b| b.expand(self.radius))
+    }
+}
+
+/// Intersection that cuts `steps` stairs spanning `radius` into the edge.
+#[derive(Debug, Clone)]
+pub struct StairsIntersect<A, B> {
+    a: A,
+    b: B,
+    radius: f64,
+    steps: usize,
+}
+
+impl<A, B> StairsIntersect<A, B> {
+    pub fn new(a: A, b: B, radius: f64, steps: usize) -> Self {
+        Self {
+            a,
+            b,
+            radius,
+            steps: steps.max(1),
+        }
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for StairsIntersect<A, B> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let (da, ma) = self.a.value_at(p);
+        let (db, mb) = self.b.value_at(p);
+        (
+            -stairs_union(-da, -db, self.radius, self.steps),
+            pick(db > da, ma, mb),
+        )
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        intersect_bounds(self.a.bounds(), self.b.bounds())
+    }
+}
+
+/// Difference that cuts `steps` stairs spanning `radius` into the edge of the cut.
+#[derive(Debug, Clone)]
+pub struct StairsDifference<A, B> {
+    a: A,
+    b: B,
+    radius: f64,
+    steps: usize,
+}
+
+impl<A, B> StairsDifference<A, B> {
+    pub fn new(a: A, b: B, radius: f64, steps: usize) -> Self {
+        Self {
+            a,
+            b,
+            radius,
+            steps: steps.max(1),
+        }
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for StairsDifference<A, B> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let (da, ma) = self.a.value_at(p);
+        let (db, mb) = self.b.value_at(p);
+        (
+            -stairs_union(-da, db, self.radius, self.steps),
+            pick(-db > da, ma, mb),
+        )
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.a.bounds()
+    }
+}
+
+/// Union that fills the crease with `columns` round columns spanning `radius`.
+#[derive(Debug, Clone)]
+pub struct ColumnsUnion<A, B> {
+    a: A,
+    b: B,
+    radius: f64,
+    columns: usize,
+}
+
+impl<A, B> ColumnsUnion<A, B> {
+    pub fn new(a: A, b: B, radius: f64, columns: usize) -> Self {
+        Self {
+            a,
+            b,
+            radius,
+            columns: columns.max(1),
+        }
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for ColumnsUnion<A, B> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let (da, ma) = self.a.value_at(p);
+        let (db, mb) = self.b.value_at(p);
+        (
+            columns_union(da, db, self.radius, self.columns),
+            pick(db < da, ma, mb),
+        )
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        union_bounds(self.a.bounds(), self.b.bounds()).map(|b| b.expand(self.radius))
+    }
+}
+
+/// Intersection that carves `columns` round grooves spanning `radius` into the edge.
+#[derive(Debug, Clone)]
+pub struct ColumnsIntersect<A, B> {
+    a: A,
+    b: B,
+    radius: f64,
+    columns: usize,
+}
+
+impl<A, B> ColumnsIntersect<A, B> {
+    pub fn new(a: A, b: B, radius: f64, columns: usize) -> Self {
+        Self {
+            a,
+            b,
+            radius,
+            columns: columns.max(1),
+        }
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for ColumnsIntersect<A, B> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let (da, ma) = self.a.value_at(p);
+        let (db, mb) = self.b.value_at(p);
+        (
+            columns_difference(da, -db, self.radius, self.columns),
+            pick(db > da, ma, mb),
+        )
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        intersect_bounds(self.a.bounds(), self.b.bounds())
+    }
+}
+
+/// Difference that carves `columns` round grooves spanning `radius` into the edge of the
+/// cut.
+#[derive(Debug, Clone)]
+pub struct ColumnsDifference<A, B> {
+    a: A,
+    b: B,
+    radius: f64,
+    columns: usize,
+}
+
+impl<A, B> ColumnsDifference<A, B> {
+    pub fn new(a: A, b: B, radius: f64, columns: usize) -> Self {
+        Self {
+            a,
+            b,
+            radius,
+            columns: columns.max(1),
+        }
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for ColumnsDifference<A, B> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let (da, ma) = self.a.value_at(p);
+        let (db, mb) = self.b.value_at(p);
+        (
+            columns_difference(da, db, self.radius, self.columns),
+            pick(-db > da, ma, mb),
+        )
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.a.bounds()
+    }
+}
+
+/// Cuts a groove of the given `depth` into `a` where it crosses the surface of `b`.
+/// The groove is `2 * width` wide and takes the material of `b`.
+#[derive(Debug, Clone)]
+pub struct Groove<A, B> {
+    a: A,
+    b: B,
+    depth: f64,
+    width: f64,
+}
+
+impl<A, B> Groove<A, B> {
+    pub fn new(a: A, b: B, depth: f64, width: f64) -> Self {
+        Self { a, b, depth, width }
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for Groove<A, B> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let (da, ma) = self.a.value_at(p);
+        let (db, mb) = self.b.value_at(p);
+        let groove = (da + self.depth).min(self.width - db.abs());
+        (da.max(groove), pick(groove > da, ma, mb))
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.a.bounds()
+    }
+}
+
+/// Adds a tongue of the given `height` onto `a` where it crosses the surface of `b`.
+/// The tongue is `2 * width` wide and takes the material of `b`.
+#[derive(Debug, Clone)]
+pub struct Tongue<A, B> {
+    a: A,
+    b: B,
+    height: f64,
+    width: f64,
+}
+
+impl<A, B> Tongue<A, B> {
+    pub fn new(a: A, b: B, height: f64, width: f64) -> Self {
+        Self {
+            a,
+            b,
+            height,
+            width,
+        }
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for Tongue<A, B> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let (da, ma) = self.a.value_at(p);
+        let (db, mb) = self.b.value_at(p);
+        let tongue = (da - self.height).max(db.abs() - self.width);
+        (da.min(tongue), pick(tongue < da, ma, mb))
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.a.bounds().map(|b| b.expand(self.height))
+    }
+}
+
+/// Engraves the surface of `b` into `a` with a V-shaped cut of the given `depth`.
+/// The engraving takes the material of `b`.
+#[derive(Debug, Clone)]
+pub struct Engrave<A, B> {
+    a: A,
+    b: B,
+    depth: f64,
+}
+
+impl<A, B> Engrave<A, B> {
+    pub fn new(a: A, b: B, depth: f64) -> Self {
+        Self { a, b, depth }
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for Engrave<A, B> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let (da, ma) = self.a.value_at(p);
+        let (db, mb) = self.b.value_at(p);
+        let engraving = (da + self.depth - db.abs()) * FRAC_1_SQRT_2;
+        (da.max(engraving), pick(engraving > da, ma, mb))
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.a.bounds()
+    }
+}
+
+/// Shape of the blend between two SDFs in the smooth combinators.
+///
+/// See https://iquilezles.org/articles/smin/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SmoothKind {
+    /// Quadratic polynomial. Only changes the distance where both SDFs are within the
+    /// blend radius of each other.
+    Polynomial,
+    /// Exponential. Gives the roundest joins, but slightly changes the distance everywhere.
+    Exponential,
+    /// Circular arc between the two surfaces.
+    Circular,
+}
+
+impl SmoothKind {
+    /// Smooth minimum of `a` and `b` with blend radius `k`, and how much `b` contributes
+    /// to the result, between 0 and 1.
+    pub fn smooth_min(self, a: f64, b: f64, k: f64) -> (f64, f64) {
+        if k <= 0.0 {
+            return if a < b { (a, 0.0) } else { (b, 1.0) };
+        }
+        let weight = (0.5 + 0.5 * (a - b) / k).clamp(0.0, 1.0);
+        match self {
+            SmoothKind::Polynomial => {
+                let d = a * (1.0 - weight) + b * weight;
+                (d - k * weight * (1.0 - weight), weight)
+            }
+            SmoothKind::Exponential => {
+                // Shift by the minimum to keep the exponentials in range.
+                let m = a.min(b);
+                let ea = (-(a - m) / k).exp2();
+                let eb = (-(b - m) / k).exp2();
+                (m - k * (ea + eb).log2(), eb / (ea + eb))
+            }
+            SmoothKind::Circular => {
+                let k = k / (1.0 - 0.5_f64.sqrt());
+                let h = (k - (a - b).abs()).max(0.0) / k;
+                (
+                    a.min(b) - k * 0.5 * (1.0 + h - (1.0 - h * (h - 2.0)).sqrt()),
+                    weight,
+                )
+            }
+        }
+    }
+
+    /// Smooth maximum, see [Self::smooth_min].
+    pub fn smooth_max(self, a: f64, b: f64, k: f64) -> (f64, f64) {
+        let (d, weight) = self.smooth_min(-a, -b, k);
+        (-d, weight)
+    }
+}
+
+/// Union that rounds off the crease where the two SDFs meet.
+#[derive(Debug, Clone)]
+pub struct SmoothUnion<A, B> {
+    a: A,
+    b: B,
+    radius: f64,
+    kind: SmoothKind,
+}
+
+impl<A, B> SmoothUnion<A, B> {
+    pub fn new(a: A, b: B, radius: f64, kind: SmoothKind) -> Self {
+        Self { a, b, radius, kind }
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for SmoothUnion<A, B> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let (da, ma) = self.a.value_at(p);
+        let (db, mb) = self.b.value_at(p);
+        let (d, weight) = self.kind.smooth_min(da, db, self.radius);
+        (d, MaterialBlend::mix(ma, mb, weight))
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        union_bounds(self.a.bounds(), self.b.bounds()).map(|b| b.expand(self.radius))
+    }
+}
+
+/// Intersection that rounds off the edge where the two SDFs meet.
+#[derive(Debug, Clone)]
+pub struct SmoothIntersect<A, B> {
+    a: A,
+    b: B,
+    radius: f64,
+    kind: SmoothKind,
+}
+
+impl<A, B> SmoothIntersect<A, B> {
+    pub fn new(a: A, b: B, radius: f64, kind: SmoothKind) -> Self {
+        Self { a, b, radius, kind }
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for SmoothIntersect<A, B> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let (da, ma) = self.a.value_at(p);
+        let (db, mb) = self.b.value_at(p);
+        let (d, weight) = self.kind.smooth_max(da, db, self.radius);
+        (d, MaterialBlend::mix(ma, mb, weight))
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        intersect_bounds(self.a.bounds(), self.b.bounds())
+    }
+}
+
+/// Difference that rounds off the edge where `b` is cut out of `a`.
+#[derive(Debug, Clone)]
+pub struct SmoothDifference<A, B> {
+    a: A,
+    b: B,
+    radius: f64,
+    kind: SmoothKind,
+}
+
+impl<A, B> SmoothDifference<A, B> {
+    pub fn new(a: A, b: B, radius: f64, kind: SmoothKind) -> Self {
+        Self { a, b, radius, kind }
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for SmoothDifference<A, B> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let (da, ma) = self.a.value_at(p);
+        let (db, mb) = self.b.value_at(p);
+        let (d, weight) = self.kind.smooth_max(da, -db, self.radius);
+        (d, MaterialBlend::mix(ma, mb, weight))
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.a.bounds()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate test;
+
+    use test::black_box;
+    use test::Bencher;
+
+    use float_cmp::ApproxEq;
+
+    use super::*;
+    use crate::scene::scenemap::sdf::primitives::Sphere;
+    use crate::test_constants::MARGIN;
+    use crate::Vec3;
+
+    /// A flat `n` by `n` grid of small spheres, as individual SDFs with their bounds.
+    fn sphere_grid(n: usize) -> Vec<(Aabb, Sphere)> {
+        (0..n * n)
+            .map(|k| {
+                let center = Point3::new((k % n) as f64, 0.0, (k / n) as f64);
+                let bounds = Aabb::from_center(&center, &Vec3::new(0.3, 0.3, 0.3));
+                (bounds, Sphere::new(0.3, center))
+            })
+            .collect()
+    }
+
+    fn multi_union(spheres: Vec<(Aabb, Sphere)>) -> MultiUnion<'static> {
+        spheres
+            .into_iter()
+            .fold(MultiUnion::new(), |acc, (bounds, s)| acc.with(bounds, s))
+    }
+
+    fn nested_union(spheres: Vec<(Aabb, Sphere)>) -> Box<dyn Sdf> {
+        spheres
+            .into_iter()
+            .map(|(_, s)| Box::new(s) as Box<dyn Sdf>)
+            .reduce(|acc, s| Box::new(Union::new(acc, s)))
+            .unwrap()
+    }
+
+    const KINDS: [SmoothKind; 3] = [
+        SmoothKind::Polynomial,
+        SmoothKind::Exponential,
+        SmoothKind::Circular,
+    ];
+
+    #[test]
+    fn smooth_min_is_below_min() {
+        for kind in KINDS.iter() {
+            let (d, weight) = kind.smooth_min(0.3, 0.3, 0.5);
+            assert!(d < 0.3);
+            assert!(weight.approx_eq(0.5, MARGIN));
+        }
+    }
+
+    #[test]
+    fn local_kinds_are_exact_outside_blend_radius() {
+        for kind in [SmoothKind::Polynomial, SmoothKind::Circular].iter() {
+            let (d, weight) = kind.smooth_min(0.1, 2.0, 0.5);
+            assert!(d.approx_eq(0.1, MARGIN));
+            assert!(weight.approx_eq(0.0, MARGIN));
+            let (d, weight) = kind.smooth_max(0.1, 2.0, 0.5);
+            assert!(d.approx_eq(2.0, MARGIN));
+            assert!(weight.approx_eq(1.0, MARGIN));
+        }
+    }
+
+    #[test]
+    fn exponential_converges_to_min() {
+        let (d, weight) = SmoothKind::Exponential.smooth_min(0.1, 20.0, 0.5);
+        assert!(d.approx_eq(0.1, MARGIN));
+        assert!(weight < 1e-5);
+    }
+
+    #[test]
+    fn smooth_union_blends_materials_at_seam() {
+        use crate::scene::scenemap::material::{Material, MaterialList};
+        use crate::scene::scenemap::sdf::primitives::Sphere;
+        use crate::scene::scenemap::sdf::WithMaterial;
+
+        let mut materials = MaterialList::new();
+        let red = materials.insert(Material::default());
+        let blue = materials.insert(Material::default());
+        let a = WithMaterial::new(Sphere::new(1.0, Point3::new(-1.0, 0.0, 0.0)), red);
+        let b = WithMaterial::new(Sphere::new(1.0, Point3::new(1.0, 0.0, 0.0)), blue);
+        let sdf = SmoothUnion::new(a, b, 0.5, SmoothKind::Polynomial);
+
+        let (_, seam) = sdf.value_at(&Point3::new(0.0, 0.5, 0.0));
+        let weights: Vec<f64> = seam.unwrap().iter().map(|(_, w)| w).collect();
+        assert_eq!(weights.len(), 2);
+        assert!(weights[0].approx_eq(0.5, MARGIN));
+
+        let (_, far) = sdf.value_at(&Point3::new(-2.5, 0.0, 0.0));
+        assert_eq!(far.unwrap().iter().collect::<Vec<_>>(), vec![(red, 1.0)]);
+    }
+
+    #[test]
+    fn multi_union_matches_nested_union() {
+        let multi = multi_union(sphere_grid(5));
+        let nested = nested_union(sphere_grid(5));
+        assert_eq!(multi.len(), 25);
+
+        for p in [
+            Point3::new(2.1, 0.2, 1.9),
+            Point3::new(-3.0, 1.0, 2.0),
+            Point3::new(1.5, 0.0, 1.5),
+            Point3::new(4.0, -0.1, 4.0),
+            Point3::new(3.0, 0.1, 3.0),
+        ]
+        .iter()
+        {
+            assert!(multi.value_at(p).0.approx_eq(nested.value_at(p).0, MARGIN));
+        }
+        assert!(MultiUnion::new().value_at(&Point3::ORIGIN).0.is_infinite());
+    }
+
+    #[bench]
+    fn multi_union_400_spheres(b: &mut Bencher) {
+        let sdf = multi_union(sphere_grid(20));
+        let p = Point3::new(7.4, 0.5, 12.2);
+        b.iter(|| black_box(&sdf).value_at(black_box(&p)))
+    }
+
+    #[bench]
+    fn nested_union_400_spheres(b: &mut Bencher) {
+        let sdf = nested_union(sphere_grid(20));
+        let p = Point3::new(7.4, 0.5, 12.2);
+        b.iter(|| black_box(&sdf).value_at(black_box(&p)))
+    }
+
+    #[test]
+    fn architectural_ops_are_exact_away_from_crease() {
+        let (a, b, r) = (0.1, 2.0, 0.5);
+        for n in 1..4 {
+            assert!(stairs_union(a, b, r, n).approx_eq(0.1, MARGIN));
+            assert!((-stairs_union(-a, -b, r, n)).approx_eq(2.0, MARGIN));
+            assert!(columns_union(a, b, r, n).approx_eq(0.1, MARGIN));
+            assert!(columns_difference(a, -b, r, n).approx_eq(2.0, MARGIN));
+        }
+        assert!(chamfer_union(a, b, r).approx_eq(0.1, MARGIN));
+        assert!(chamfer_intersect(a, b, r).approx_eq(2.0, MARGIN));
+    }
+
+    #[test]
+    fn architectural_ops_fill_crease() {
+        let r = 0.5;
+        assert!(chamfer_union(0.0, 0.0, r).approx_eq(-r * FRAC_1_SQRT_2, MARGIN));
+        assert!(chamfer_intersect(0.0, 0.0, r).approx_eq(r * FRAC_1_SQRT_2, MARGIN));
+        for n in 2..5 {
+            assert!(stairs_union(0.0, 0.0, r, n) < 0.0);
+            assert!(columns_union(0.0, 0.0, r, n) <= 0.0);
+        }
+    }
+
+    #[test]
+    fn groove_cuts_along_other_surface_with_its_material() {
+        use crate::scene::scenemap::material::{Material, MaterialList};
+        use crate::scene::scenemap::sdf::primitives::{NegY, Sphere};
+        use crate::scene::scenemap::sdf::WithMaterial;
+
+        let mut materials = MaterialList::new();
+        let floor = materials.insert(Material::default());
+        let cutter = materials.insert(Material::default());
+        let groove = Groove::new(
+            WithMaterial::new(NegY, floor),
+            WithMaterial::new(Sphere::default(), cutter),
+            0.2,
+            0.1,
+        );
+        let tongue = Tongue::new(NegY, Sphere::default(), 0.2, 0.1);
+
+        // On the floor where the sphere crosses it
+        let (d, m) = groove.value_at(&Point3::new(1.0, 0.0, 0.0));
+        assert!(d > 0.0);
+        assert_eq!(m.unwrap().dominant(), cutter);
+        assert!(tongue.value_at(&Point3::new(1.0, 0.0, 0.0)).0 < 0.0);
+
+        // On the floor away from the sphere
+        let (d, m) = groove.value_at(&Point3::new(2.0, 0.0, 0.0));
+        assert!(d.approx_eq(0.0, MARGIN));
+        assert_eq!(m.unwrap().dominant(), floor);
+        assert!(tongue
+            .value_at(&Point3::new(2.0, 0.0, 0.0))
+            .0
+            .approx_eq(0.0, MARGIN));
+    }
 }
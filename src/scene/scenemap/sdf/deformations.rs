@@ -0,0 +1,384 @@
+//! Positioners that deform space rather than moving it rigidly.
+//!
+//! Deformations stretch distances, so the SDF values they produce are divided by a
+//! Lipschitz factor, a bound on how much the deformation stretches space. That keeps
+//! [crate::Ray::find_target] from stepping past the surface, at the cost of more steps.
+//! The factors are derived from the deformation parameters and the bounds of the deformed
+//! SDF where possible.
+
+use crate::scene::scenemap::material::MaterialBlend;
+use crate::scene::scenemap::sdf::positioners::Axis;
+use crate::scene::scenemap::sdf::Sdf;
+use crate::{Aabb, Point3, Vec3};
+
+/// Largest stretch of a shear by `a`, i.e. the largest singular value of `[[1, a], [0, 1]]`.
+fn shear_stretch(a: f64) -> f64 {
+    (a + (a * a + 4.0).sqrt()) / 2.0
+}
+
+/// Largest distance of the corners of `bounds` from `axis`, or [None] if that is
+/// unbounded.
+fn radius_around(bounds: &Option<Aabb>, axis: Axis) -> Option<f64> {
+    let radius = bounds
+        .as_ref()?
+        .corners()
+        .iter()
+        .map(|c| {
+            let (_, u, v) = axis.split(&c.0);
+            u.hypot(v)
+        })
+        .fold(0.0, f64::max);
+    Some(radius).filter(|r| r.is_finite())
+}
+
+/// Range covered by `bounds` along `axis`, or [None] if that is unbounded.
+fn range_along(bounds: &Option<Aabb>, axis: Axis) -> Option<(f64, f64)> {
+    let bounds = bounds.as_ref()?;
+    let (min, ..) = axis.split(&bounds.min.0);
+    let (max, ..) = axis.split(&bounds.max.0);
+    Some((min, max)).filter(|(min, max)| min.is_finite() && max.is_finite())
+}
+
+/// Box around the cylinder with the given radius around `axis`, spanning `range` along it.
+fn cylinder_bounds(axis: Axis, radius: f64, range: (f64, f64)) -> Aabb {
+    Aabb::new(
+        Point3(axis.join(range.0, -radius, -radius)),
+        Point3(axis.join(range.1, radius, radius)),
+    )
+}
+
+/// Twists an SDF around an axis through the origin, by `rate` radians per unit along it.
+#[derive(Debug, Clone)]
+pub struct Twist<A> {
+    a: A,
+    axis: Axis,
+    rate: f64,
+    /// Largest distance of the SDF from the axis, if it is bounded.
+    radius: Option<f64>,
+}
+
+impl<A: Sdf> Twist<A> {
+    pub fn new(a: A, axis: Axis, rate: f64) -> Self {
+        let radius = radius_around(&a.bounds(), axis);
+        Self {
+            a,
+            axis,
+            rate,
+            radius,
+        }
+    }
+}
+
+impl<A: Sdf> Sdf for Twist<A> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let (along, u, v) = self.axis.split(&p.0);
+        let (sin, cos) = (-self.rate * along).sin_cos();
+        let q = Point3(self.axis.join(along, cos * u - sin * v, sin * u + cos * v));
+        let (d, m) = self.a.value_at(&q);
+
+        // The twist shears space by the rate times the distance to the axis. The way from
+        // `p` to the surface stays within the larger of both distances.
+        let r = u.hypot(v).max(self.radius.unwrap_or(0.0));
+        (d / shear_stretch(self.rate.abs() * r), m)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let bounds = self.a.bounds();
+        Some(cylinder_bounds(
+            self.axis,
+            self.radius?,
+            range_along(&bounds, self.axis)?,
+        ))
+    }
+}
+
+/// Bends an SDF in the xy plane: points are rotated around the z axis by `rate` radians
+/// per unit along x, so the x axis curves into a circle of radius `1 / rate`.
+#[derive(Debug, Clone)]
+pub struct Bend<A> {
+    a: A,
+    rate: f64,
+    /// Largest distance of the SDF from the z axis, if it is bounded.
+    radius: Option<f64>,
+}
+
+impl<A: Sdf> Bend<A> {
+    pub fn new(a: A, rate: f64) -> Self {
+        let radius = radius_around(&a.bounds(), Axis::Z);
+        Self { a, rate, radius }
+    }
+}
+
+impl<A: Sdf> Sdf for Bend<A> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let Vec3 { x, y, z } = p.0;
+        let (sin, cos) = (self.rate * x).sin_cos();
+        let (d, m) = self
+            .a
+            .value_at(&Point3::new(cos * x - sin * y, sin * x + cos * y, z));
+
+        let r = x.hypot(y).max(self.radius.unwrap_or(0.0));
+        (d / (1.0 + self.rate.abs() * r), m)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let bounds = self.a.bounds();
+        Some(cylinder_bounds(
+            Axis::Z,
+            self.radius?,
+            range_along(&bounds, Axis::Z)?,
+        ))
+    }
+}
+
+/// Smallest cross-section scale a [Taper] applies, to keep it from turning inside out.
+const MIN_TAPER_SCALE: f64 = 0.01;
+
+/// Scales the cross-sections of an SDF perpendicular to an axis by `1 + rate * h`, where
+/// `h` is the position along the axis.
+///
+/// For bounded SDFs the taper only applies within their extent along the axis, beyond it
+/// the scale stays constant.
+#[derive(Debug, Clone)]
+pub struct Taper<A> {
+    a: A,
+    axis: Axis,
+    rate: f64,
+    range: Option<(f64, f64)>,
+    /// Largest distance of the SDF from the axis, if it is bounded.
+    radius: Option<f64>,
+}
+
+impl<A: Sdf> Taper<A> {
+    pub fn new(a: A, axis: Axis, rate: f64) -> Self {
+        let bounds = a.bounds();
+        Self {
+            range: range_along(&bounds, axis),
+            radius: radius_around(&bounds, axis),
+            a,
+            axis,
+            rate,
+        }
+    }
+
+    fn scale(&self, along: f64) -> f64 {
+        (1.0 + self.rate * along).max(MIN_TAPER_SCALE)
+    }
+
+    /// Smallest and largest scale applied anywhere near `along`.
+    fn scale_range(&self, along: f64) -> (f64, f64) {
+        match self.range {
+            Some((min, max)) => {
+                let (a, b) = (self.scale(min), self.scale(max));
+                (a.min(b), a.max(b))
+            }
+            None => (self.scale(along), self.scale(along)),
+        }
+    }
+}
+
+impl<A: Sdf> Sdf for Taper<A> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let (along, u, v) = self.axis.split(&p.0);
+        let h = match self.range {
+            Some((min, max)) => along.clamp(min, max),
+            None => along,
+        };
+        let s = self.scale(h);
+        let (d, m) = self
+            .a
+            .value_at(&Point3(self.axis.join(along, u / s, v / s)));
+
+        // Scaling by `1 / s` stretches by at most `1 / s_min`, and the change of scale
+        // along the axis shears by `rate * r / s²`.
+        let (s_min, s_max) = self.scale_range(along);
+        let r = u.hypot(v).max(self.radius.unwrap_or(0.0) * s_max);
+        let lipschitz = (1.0 / s_min).max(1.0) + self.rate.abs() * r / (s_min * s_min);
+        (d / lipschitz, m)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let range = self.range?;
+        let (_, s_max) = self.scale_range(range.0);
+        Some(cylinder_bounds(self.axis, self.radius? * s_max, range))
+    }
+}
+
+/// Adds a displacement `f(p)` to an SDF, e.g. to add ripples or noise to its surface.
+///
+/// The result is divided by `1 + max_gradient`, where `max_gradient` bounds the length of
+/// the gradient of `f`.
+pub struct Displace<A, F> {
+    a: A,
+    f: F,
+    lipschitz: f64,
+}
+
+impl<A, F> Displace<A, F>
+where
+    A: Sdf,
+    F: Fn(&Point3) -> f64 + Send + Sync,
+{
+    /// `max_gradient` must bound the length of the gradient of `f` everywhere.
+    pub fn new(a: A, f: F, max_gradient: f64) -> Self {
+        Self {
+            a,
+            f,
+            lipschitz: 1.0 + max_gradient.abs(),
+        }
+    }
+
+    /// Estimates the gradient bound by sampling `f` on a grid within `region`, with some
+    /// margin for peaks between the samples. Only valid if `f` doesn't change faster
+    /// elsewhere, e.g. because it is periodic or `region` covers everything near the SDF.
+    pub fn sampled(a: A, f: F, region: &Aabb) -> Self {
+        let max_gradient = estimate_gradient(&f, region);
+        Self::new(a, f, SAMPLED_GRADIENT_MARGIN * max_gradient)
+    }
+}
+
+/// Samples per axis used by [Displace::sampled].
+const GRADIENT_SAMPLES: usize = 16;
+
+/// Factor applied to the largest sampled gradient.
+const SAMPLED_GRADIENT_MARGIN: f64 = 1.25;
+
+fn estimate_gradient(f: &impl Fn(&Point3) -> f64, region: &Aabb) -> f64 {
+    let h = 1e-5;
+    let size = region.size();
+    let at = |i: usize, n: usize| (i as f64 + 0.5) / n as f64;
+    let n = GRADIENT_SAMPLES;
+    (0..n * n * n)
+        .map(|k| {
+            let offset = Vec3::new(at(k % n, n), at(k / n % n, n), at(k / (n * n), n));
+            let p = &region.min + &(&size * &offset);
+            let diff = |dx: f64, dy: f64, dz: f64| {
+                let step = Vec3::new(dx, dy, dz);
+                (f(&(&p + &step)) - f(&(&p - &step))) / (2.0 * h)
+            };
+            Vec3::new(diff(h, 0.0, 0.0), diff(0.0, h, 0.0), diff(0.0, 0.0, h)).length()
+        })
+        .fold(0.0, f64::max)
+}
+
+impl<A, F> Sdf for Displace<A, F>
+where
+    A: Sdf,
+    F: Fn(&Point3) -> f64 + Send + Sync,
+{
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let (d, m) = self.a.value_at(p);
+        ((d + (self.f)(p)) / self.lipschitz, m)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::ApproxEq;
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::scene::scenemap::sdf::positioners::Scale;
+    use crate::scene::scenemap::sdf::primitives::{Cube, NegY};
+    use crate::test_constants::MARGIN;
+
+    fn slab() -> Cube {
+        Cube::new_half_side(0.5, Point3::ORIGIN)
+    }
+
+    fn beam() -> Scale<Cube> {
+        Scale::new(slab(), Vec3::new(4.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn twist_rotates_with_height() {
+        let sdf = Twist::new(
+            Scale::new(slab(), Vec3::new(4.0, 4.0, 0.5)),
+            Axis::Y,
+            std::f64::consts::FRAC_PI_2,
+        );
+
+        // At y = 0 the long side is along x, at y = 1 it has turned to z
+        assert!(sdf.value_at(&Point3::new(1.5, 0.0, 0.0)).0 < 0.0);
+        assert!(sdf.value_at(&Point3::new(1.5, 1.0, 0.0)).0 > 0.0);
+        assert!(sdf.value_at(&Point3::new(0.0, 1.0, 1.5)).0 < 0.0);
+    }
+
+    #[test]
+    fn bend_curves_x_axis() {
+        let rate = 0.25;
+        let sdf = Bend::new(beam(), rate);
+
+        assert!(sdf.value_at(&Point3::ORIGIN).0 < 0.0);
+        // The end of the beam moves down, following the circle
+        let end = 1.5 * rate;
+        let (sin, cos) = end.sin_cos();
+        assert!(sdf.value_at(&Point3::new(1.5 * cos, -1.5 * sin, 0.0)).0 < 0.0);
+        assert!(sdf.value_at(&Point3::new(1.5, 0.0, 0.0)).0 > 0.0);
+    }
+
+    #[test]
+    fn taper_scales_cross_section() {
+        let sdf = Taper::new(beam(), Axis::X, -0.4);
+
+        // Full size at x = 0, half size at x = 1.25
+        assert!(sdf
+            .value_at(&Point3::new(0.0, 0.5, 0.0))
+            .0
+            .approx_eq(0.0, MARGIN));
+        assert!(sdf
+            .value_at(&Point3::new(1.25, 0.25, 0.0))
+            .0
+            .approx_eq(0.0, MARGIN));
+        let bounds = sdf.bounds().unwrap();
+        assert!(bounds.max.0.y >= 0.5 * 1.8);
+    }
+
+    #[test]
+    fn displace_moves_surface() {
+        let sdf = Displace::new(NegY, |p: &Point3| -p.0.x.sin(), 1.0);
+        let x = 1.0_f64;
+        assert!(sdf
+            .value_at(&Point3::new(x, x.sin(), 0.0))
+            .0
+            .approx_eq(0.0, MARGIN));
+
+        let sampled = Displace::sampled(
+            NegY,
+            |p: &Point3| 3.0 * p.0.x,
+            &Aabb::new(Point3::ORIGIN, Point3::new(1.0, 1.0, 1.0)),
+        );
+        assert!(sampled
+            .lipschitz
+            .approx_eq(1.0 + 3.0 * SAMPLED_GRADIENT_MARGIN, MARGIN));
+    }
+
+    prop_compose! {
+        fn arb_point()(x in -3.0..3.0, y in -3.0..3.0, z in -3.0..3.0) -> Point3 {
+            Point3::new(x, y, z)
+        }
+    }
+
+    fn lipschitz(sdf: &dyn Sdf, p: &Point3, step: &Vec3) -> bool {
+        let q = p + step;
+        (sdf.value_at(p).0 - sdf.value_at(&q).0).abs() <= step.length() + 1e-9
+    }
+
+    proptest! {
+        #[test]
+        fn deformations_never_overestimate(
+            p in arb_point(),
+            dx in -0.05..0.05, dy in -0.05..0.05, dz in -0.05..0.05,
+            rate in -1.0..1.0,
+        ) {
+            let step = Vec3::new(dx, dy, dz);
+            prop_assert!(lipschitz(&Twist::new(beam(), Axis::X, rate), &p, &step));
+            prop_assert!(lipschitz(&Twist::new(beam(), Axis::Y, 2.0 * rate), &p, &step));
+            prop_assert!(lipschitz(&Bend::new(beam(), rate), &p, &step));
+            prop_assert!(lipschitz(&Taper::new(beam(), Axis::X, 0.3 * rate), &p, &step));
+            prop_assert!(lipschitz(&Taper::new(slab(), Axis::Y, rate), &p, &step));
+            let ripple = Displace::new(slab(), move |p: &Point3| 0.3 * (4.0 * p.0.x).sin() * rate, 1.2 * rate.abs());
+            prop_assert!(lipschitz(&ripple, &p, &step));
+        }
+    }
+}
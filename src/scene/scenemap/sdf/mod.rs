@@ -1,16 +1,33 @@
 use crate::primitives::{Point3, UnitVec3};
-use crate::scene::scenemap::material::MaterialIndex;
-use crate::Vec3;
+use crate::scene::scenemap::material::{MaterialBlend, MaterialIndex};
+use crate::{Aabb, Vec3};
 use std::ops::Deref;
-use std::rc::Rc;
 use std::sync::Arc;
 
+pub mod bvh;
 pub mod combinators;
+pub mod deformations;
+pub mod modifiers;
+pub mod paths;
 pub mod positioners;
 pub mod primitives;
+pub mod sdf2d;
 
-pub trait Sdf {
-    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialIndex>);
+/// Signed distance function describing (part of) a scene.
+///
+/// Scenes are rendered from multiple threads, so implementors must be [Send] and [Sync].
+pub trait Sdf: Send + Sync {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>);
+
+    /// Box containing every point where the SDF is negative, or [None] if it is unbounded.
+    ///
+    /// Bounds may be larger than the object, but never smaller, so anything farther from
+    /// the box than some distance is at least that far from the object too. Half spaces
+    /// and the like that are bounded on some sides only return boxes with infinite sides,
+    /// but a box that is infinite on every side is never returned: that is [None].
+    fn bounds(&self) -> Option<Aabb> {
+        None
+    }
 
     /// Does a 6-point numerical gradient by default.
     ///
@@ -55,28 +72,40 @@ pub trait Sdf {
     }
 }
 
-impl<'a, A: Sdf> Sdf for &'a A {
-    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialIndex>) {
+impl<A: Sdf + ?Sized> Sdf for &A {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
         (*self).value_at(p)
     }
+
+    fn bounds(&self) -> Option<Aabb> {
+        (*self).bounds()
+    }
 }
 
 impl<A: Sdf + ?Sized> Sdf for Box<A> {
-    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialIndex>) {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
         (self.deref()).value_at(p)
     }
-}
 
-impl<A: Sdf + ?Sized> Sdf for Rc<A> {
-    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialIndex>) {
-        (self.deref()).value_at(p)
+    fn bounds(&self) -> Option<Aabb> {
+        (self.deref()).bounds()
     }
 }
 
 impl<A: Sdf + ?Sized> Sdf for Arc<A> {
-    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialIndex>) {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
         (self.deref()).value_at(p)
     }
+
+    fn bounds(&self) -> Option<Aabb> {
+        (self.deref()).bounds()
+    }
+}
+
+/// `bounds` as returned from [Sdf::bounds], which uses [None] for boxes that are infinite
+/// on every side.
+fn bounded(bounds: Aabb) -> Option<Aabb> {
+    Some(bounds).filter(|b| !b.is_unbounded())
 }
 
 #[derive(Debug, Clone)]
@@ -92,7 +121,171 @@ impl<A> WithMaterial<A> {
 }
 
 impl<A: Sdf> Sdf for WithMaterial<A> {
-    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialIndex>) {
-        (self.a.value_at(p).0, Some(self.m))
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        (self.a.value_at(p).0, Some(self.m.into()))
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.a.bounds()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::bvh::Bvh;
+    use super::combinators::*;
+    use super::modifiers::{Elongate, Invert, Onion, Round};
+    use super::positioners::{Repeat, RepeatMirrored, Rotate, Scale, ScaleUniform, Translate};
+    use super::primitives::{Cube, NegY, Plane, Sphere};
+    use super::*;
+
+    fn composed() -> impl Sdf {
+        let rotated = Rotate::new_degrees(
+            Cube::new(1.0, Point3::new(0.5, 0.0, 0.0)),
+            30.0,
+            Vec3::new(1.0, 1.0, 0.0).unit(),
+        );
+        let moved = Translate::new(
+            ScaleUniform::new(Sphere::default(), 0.5),
+            Vec3::new(0.0, 1.0, 0.5),
+        );
+        let blob = SmoothUnion::new(rotated, moved, 0.3, SmoothKind::Exponential);
+        let chamfered = ChamferUnion::new(blob, Sphere::new(0.3, Point3::new(-1.0, 0.0, 0.0)), 0.2);
+        Difference::new(
+            Intersect::new(chamfered, Translate::new(NegY, Vec3::new(0.0, 1.2, 0.0))),
+            Sphere::new(0.2, Point3::ORIGIN),
+        )
+    }
+
+    #[test]
+    fn composed_bounds_are_finite() {
+        let bounds = composed().bounds().unwrap();
+        assert!(bounds.is_finite());
+        // Cut off by the half space
+        assert!(bounds.max.0.y <= 1.2);
+    }
+
+    /// How far an SDF extends according to its bounds.
+    #[derive(Debug, PartialEq)]
+    enum Extent {
+        Bounded,
+        Half,
+        Unbounded,
+    }
+
+    fn extent(sdf: &dyn Sdf) -> Extent {
+        match sdf.bounds() {
+            None => Extent::Unbounded,
+            Some(b) if b.is_finite() => Extent::Bounded,
+            Some(b) => {
+                assert!(!b.is_unbounded(), "infinite box instead of None: {:?}", b);
+                Extent::Half
+            }
+        }
+    }
+
+    type Shape = Box<dyn Sdf>;
+
+    fn ball() -> Shape {
+        Box::new(Sphere::default())
+    }
+
+    fn below() -> Shape {
+        Box::new(NegY)
+    }
+
+    fn above() -> Shape {
+        Box::new(Plane::new(Vec3::new(0.0, -1.0, 0.0).unit(), 0.0))
+    }
+
+    fn everywhere() -> Shape {
+        Box::new(Invert::new(Sphere::default()))
+    }
+
+    #[test]
+    fn unbounded_is_none_through_every_combinator() {
+        use Extent::*;
+        let unions: Vec<fn(Shape, Shape) -> Shape> = vec![
+            |a, b| Box::new(Union::new(a, b)),
+            |a, b| Box::new(ChamferUnion::new(a, b, 0.1)),
+            |a, b| Box::new(StairsUnion::new(a, b, 0.1, 3)),
+            |a, b| Box::new(ColumnsUnion::new(a, b, 0.1, 3)),
+            |a, b| Box::new(SmoothUnion::new(a, b, 0.1, SmoothKind::Exponential)),
+            |a, b| {
+                let union = MultiUnion::new()
+                    .with(a.bounds().unwrap_or(Aabb::INFINITE), a)
+                    .with(b.bounds().unwrap_or(Aabb::INFINITE), b);
+                Box::new(Bvh::from(union))
+            },
+        ];
+        for union in unions {
+            assert_eq!(extent(&union(ball(), ball())), Bounded);
+            assert_eq!(extent(&union(ball(), below())), Half);
+            assert_eq!(extent(&union(ball(), everywhere())), Unbounded);
+            assert_eq!(extent(&union(below(), above())), Unbounded);
+        }
+
+        let intersections: Vec<fn(Shape, Shape) -> Shape> = vec![
+            |a, b| Box::new(Intersect::new(a, b)),
+            |a, b| Box::new(ChamferIntersect::new(a, b, 0.1)),
+            |a, b| Box::new(StairsIntersect::new(a, b, 0.1, 3)),
+            |a, b| Box::new(ColumnsIntersect::new(a, b, 0.1, 3)),
+            |a, b| Box::new(SmoothIntersect::new(a, b, 0.1, SmoothKind::Polynomial)),
+        ];
+        for intersection in intersections {
+            assert_eq!(extent(&intersection(ball(), everywhere())), Bounded);
+            assert_eq!(extent(&intersection(below(), above())), Half);
+            assert_eq!(extent(&intersection(everywhere(), below())), Half);
+            assert_eq!(extent(&intersection(everywhere(), everywhere())), Unbounded);
+        }
+
+        // Everything else keeps the extent of `a`
+        let others: Vec<fn(Shape, Shape) -> Shape> = vec![
+            |a, b| Box::new(Difference::new(a, b)),
+            |a, b| Box::new(ChamferDifference::new(a, b, 0.1)),
+            |a, b| Box::new(StairsDifference::new(a, b, 0.1, 3)),
+            |a, b| Box::new(ColumnsDifference::new(a, b, 0.1, 3)),
+            |a, b| Box::new(SmoothDifference::new(a, b, 0.1, SmoothKind::Circular)),
+            |a, b| Box::new(Groove::new(a, b, 0.1, 0.1)),
+            |a, b| Box::new(Tongue::new(a, b, 0.1, 0.1)),
+            |a, b| Box::new(Engrave::new(a, b, 0.1)),
+            |a, _| Box::new(Translate::new(a, Vec3::new(1.0, 2.0, 3.0))),
+            |a, _| Box::new(ScaleUniform::new(a, 2.0)),
+            |a, _| Box::new(Scale::new(a, Vec3::new(1.0, 2.0, 3.0))),
+            |a, _| Box::new(Round::new(a, 0.1)),
+            |a, _| Box::new(Onion::new(a, 0.1)),
+            |a, _| Box::new(Elongate::new(a, Vec3::new(0.1, 0.2, 0.3))),
+        ];
+        for other in others {
+            assert_eq!(extent(&other(ball(), everywhere())), Bounded);
+            assert_eq!(extent(&other(below(), ball())), Half);
+            assert_eq!(extent(&other(everywhere(), ball())), Unbounded);
+        }
+
+        let repeated = |period: Vec3| -> Vec<Shape> {
+            vec![
+                Box::new(Repeat::new(below(), period.clone())),
+                Box::new(RepeatMirrored::new(below(), period)),
+            ]
+        };
+        for sdf in repeated(Vec3::new(1.0, 0.0, 1.0)) {
+            assert_eq!(extent(&sdf), Half);
+        }
+        for sdf in repeated(Vec3::new(1.0, 1.0, 1.0)) {
+            assert_eq!(extent(&sdf), Unbounded);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn outside_bounds_is_outside_object(x in -4.0..4.0, y in -4.0..4.0, z in -4.0..4.0) {
+            let sdf = composed();
+            let p = Point3::new(x, y, z);
+            if sdf.bounds().unwrap().distance_to(&p) > 0.0 {
+                prop_assert!(sdf.value_at(&p).0 > 0.0);
+            }
+        }
     }
 }
@@ -0,0 +1,172 @@
+//! Modifiers that change the shape of a single SDF by adjusting its distances.
+
+use crate::scene::scenemap::material::MaterialBlend;
+use crate::scene::scenemap::sdf::Sdf;
+use crate::{Aabb, Point3, Vec3};
+
+/// Rounds off all edges and corners by moving the surface outwards by `radius`.
+///
+/// To keep the original size, round a version of the SDF that is `radius` smaller.
+#[derive(Debug, Clone)]
+pub struct Round<A> {
+    a: A,
+    radius: f64,
+}
+
+impl<A> Round<A> {
+    pub fn new(a: A, radius: f64) -> Self {
+        Self { a, radius }
+    }
+}
+
+impl<A: Sdf> Sdf for Round<A> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let (d, m) = self.a.value_at(p);
+        (d - self.radius, m)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.a.bounds().map(|b| b.expand(self.radius.max(0.0)))
+    }
+}
+
+/// Hollows out an SDF, leaving a shell of `2 * thickness` centered on its surface.
+///
+/// Onions can be nested to get multiple concentric shells.
+#[derive(Debug, Clone)]
+pub struct Onion<A> {
+    a: A,
+    thickness: f64,
+}
+
+impl<A> Onion<A> {
+    pub fn new(a: A, thickness: f64) -> Self {
+        Self { a, thickness }
+    }
+}
+
+impl<A: Sdf> Sdf for Onion<A> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let (d, m) = self.a.value_at(p);
+        (d.abs() - self.thickness, m)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.a.bounds().map(|b| b.expand(self.thickness.max(0.0)))
+    }
+}
+
+/// Stretches an SDF by cutting it through the origin and inserting a straight section of
+/// `2 * h` along each axis, e.g. to turn a sphere into a capsule.
+#[derive(Debug, Clone)]
+pub struct Elongate<A> {
+    a: A,
+    h: Vec3,
+}
+
+impl<A> Elongate<A> {
+    pub fn new(a: A, h: Vec3) -> Self {
+        Self { a, h: h.abs() }
+    }
+}
+
+impl<A: Sdf> Sdf for Elongate<A> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let clamped = p.0.max(&-&self.h).min(&self.h);
+        self.a.value_at(&Point3(&p.0 - &clamped))
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.a.bounds().map(|b| Aabb {
+            min: &b.min - &self.h,
+            max: &b.max + &self.h,
+        })
+    }
+}
+
+/// Swaps inside and outside, turning an object into a cavity in an infinite solid.
+#[derive(Debug, Clone)]
+pub struct Invert<A> {
+    a: A,
+}
+
+impl<A> Invert<A> {
+    pub fn new(a: A) -> Self {
+        Self { a }
+    }
+}
+
+impl<A: Sdf> Sdf for Invert<A> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let (d, m) = self.a.value_at(p);
+        (-d, m)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::ApproxEq;
+
+    use super::*;
+    use crate::scene::scenemap::material::{Material, MaterialList};
+    use crate::scene::scenemap::sdf::primitives::{Cube, Sphere};
+    use crate::scene::scenemap::sdf::WithMaterial;
+    use crate::test_constants::MARGIN;
+
+    #[test]
+    fn round_rounds_corners() {
+        let sdf = Round::new(Cube::new(1.0, Point3::ORIGIN), 0.25);
+
+        assert!(sdf
+            .value_at(&Point3::new(0.75, 0.0, 0.0))
+            .0
+            .approx_eq(0.0, MARGIN));
+        // Along the diagonal the corner is a quarter sphere around the old corner
+        let corner = 0.5 + 0.25 / 3.0_f64.sqrt();
+        assert!(sdf
+            .value_at(&Point3::new(corner, corner, corner))
+            .0
+            .approx_eq(0.0, MARGIN));
+    }
+
+    #[test]
+    fn nested_onions_make_layered_shells() {
+        let mut materials = MaterialList::new();
+        let m = materials.insert(Material::default());
+        let sdf = Onion::new(
+            Onion::new(WithMaterial::new(Sphere::default(), m), 0.2),
+            0.05,
+        );
+        let d = |r: f64| sdf.value_at(&Point3::new(r, 0.0, 0.0));
+
+        // Shells around radius 0.8 and 1.2
+        assert!(d(0.8).0.approx_eq(-0.05, MARGIN));
+        assert!(d(1.2).0.approx_eq(-0.05, MARGIN));
+        assert!(d(1.0).0.approx_eq(0.15, MARGIN));
+        assert!(d(0.0).0 > 0.0);
+        assert_eq!(d(1.2).1.unwrap().dominant(), m);
+    }
+
+    #[test]
+    fn elongated_sphere_is_capsule() {
+        let sdf = Elongate::new(Sphere::new(0.5, Point3::ORIGIN), Vec3::new(0.0, 1.0, 0.0));
+
+        assert!(sdf
+            .value_at(&Point3::new(0.5, 0.7, 0.0))
+            .0
+            .approx_eq(0.0, MARGIN));
+        assert!(sdf
+            .value_at(&Point3::new(0.0, 2.0, 0.0))
+            .0
+            .approx_eq(0.5, MARGIN));
+        let bounds = sdf.bounds().unwrap();
+        assert!(bounds.max.0.y.approx_eq(1.5, MARGIN));
+    }
+
+    #[test]
+    fn invert_swaps_inside_and_outside() {
+        let sdf = Invert::new(Sphere::default());
+        assert!(sdf.value_at(&Point3::ORIGIN).0.approx_eq(1.0, MARGIN));
+        assert!(sdf.bounds().is_none());
+    }
+}
@@ -0,0 +1,744 @@
+//! Exact distances to polygons, Bezier paths and tubes along curves.
+//!
+//! Curves are made of straight lines and quadratic or cubic Bezier segments. Closed 2D
+//! curves form the contours of a [Path], whose inside is decided by a [FillRule], while an
+//! open 3D curve can be thickened into a [Tube] for pipes and wires.
+
+use std::marker::PhantomData;
+
+use crate::scene::scenemap::material::MaterialBlend;
+use crate::scene::scenemap::sdf::primitives::segment_distance_squared;
+use crate::scene::scenemap::sdf::sdf2d::Sdf2d;
+use crate::scene::scenemap::sdf::Sdf;
+use crate::{Aabb, Point3, Vec2, Vec3};
+
+/// Bisection steps used to refine roots, which gets them to within `2^-48` of the
+/// parameter range.
+const BISECTIONS: usize = 48;
+
+/// Polygon through `vertices`, closed by an edge from the last vertex back to the first.
+///
+/// The polygon may be concave. Self-intersecting polygons are filled with the even-odd
+/// rule; use a [Path] to pick a different [FillRule].
+#[derive(Debug, Clone)]
+pub struct Polygon {
+    vertices: Vec<Vec2>,
+}
+
+impl Polygon {
+    pub fn new(vertices: Vec<Vec2>) -> Self {
+        Self { vertices }
+    }
+}
+
+impl Sdf2d for Polygon {
+    fn distance_at(&self, p: Vec2) -> f64 {
+        let mut distance_squared = f64::INFINITY;
+        let mut inside = false;
+        let n = self.vertices.len();
+        for i in 0..n {
+            let (a, b) = (self.vertices[i], self.vertices[(i + n - 1) % n]);
+            let (edge, offset) = (b - a, p - a);
+            let along = if edge.length_squared() > 0.0 {
+                (offset.dot(edge) / edge.length_squared()).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            distance_squared = distance_squared.min((offset - edge * along).length_squared());
+
+            // Count crossings of the ray from p towards +x
+            let above_a = p.y >= a.y;
+            let below_b = p.y < b.y;
+            let turns_left = edge.cross(offset) > 0.0;
+            if above_a == below_b && below_b == turns_left {
+                inside = !inside;
+            }
+        }
+        let distance = distance_squared.sqrt();
+        if inside {
+            -distance
+        } else {
+            distance
+        }
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Aabb::from_points(self.vertices.iter().map(|v| Point3::new(v.x, v.y, 0.0)))
+    }
+}
+
+/// Point type a [Curve] can be built from: [Vec2] for 2D paths, [Point3] for tubes.
+pub trait CurvePoint: Clone {
+    fn to_vec3(&self) -> Vec3;
+}
+
+impl CurvePoint for Vec2 {
+    fn to_vec3(&self) -> Vec3 {
+        Vec3::new(self.x, self.y, 0.0)
+    }
+}
+
+impl CurvePoint for Point3 {
+    fn to_vec3(&self) -> Vec3 {
+        self.0.clone()
+    }
+}
+
+/// Connected sequence of straight lines and Bezier segments, starting at a given point.
+///
+/// Each segment starts where the previous one ended, like the path commands of SVG.
+#[derive(Debug, Clone)]
+pub struct Curve<P> {
+    start: Vec3,
+    end: Vec3,
+    segments: Vec<Segment>,
+    bounds: Aabb,
+    point: PhantomData<P>,
+}
+
+impl<P: CurvePoint> Curve<P> {
+    pub fn new(start: P) -> Self {
+        let start = start.to_vec3();
+        Self {
+            end: start.clone(),
+            bounds: Aabb::new(Point3(start.clone()), Point3(start.clone())),
+            start,
+            segments: Vec::new(),
+            point: PhantomData,
+        }
+    }
+
+    pub fn line_to(self, to: P) -> Self {
+        let to = to.to_vec3();
+        let segment = Segment::Line(self.end.clone(), to.clone());
+        self.push(segment, vec![to])
+    }
+
+    pub fn quadratic_to(self, control: P, to: P) -> Self {
+        let (control, to) = (control.to_vec3(), to.to_vec3());
+        let segment = Segment::Quadratic(self.end.clone(), control.clone(), to.clone());
+        self.push(segment, vec![control, to])
+    }
+
+    pub fn cubic_to(self, control1: P, control2: P, to: P) -> Self {
+        let (control1, control2, to) = (control1.to_vec3(), control2.to_vec3(), to.to_vec3());
+        let segment = Segment::Cubic(
+            self.end.clone(),
+            control1.clone(),
+            control2.clone(),
+            to.clone(),
+        );
+        self.push(segment, vec![control1, control2, to])
+    }
+
+    /// Bezier segments stay inside the convex hull of their control points, so those are
+    /// enough to bound the curve.
+    fn push(mut self, segment: Segment, points: Vec<Vec3>) -> Self {
+        self.end = points[points.len() - 1].clone();
+        for point in points {
+            self.bounds = self
+                .bounds
+                .union(&Aabb::new(Point3(point.clone()), Point3(point)));
+        }
+        self.segments.push(segment);
+        self
+    }
+}
+
+impl<P> Curve<P> {
+    fn distance_squared(&self, p: &Vec3) -> f64 {
+        self.segments
+            .iter()
+            .map(|s| s.distance_squared(p))
+            .fold(f64::INFINITY, f64::min)
+    }
+}
+
+impl Curve<Vec2> {
+    /// Straight line back to the start, which closes the curve into a contour.
+    fn closing_segment(&self) -> Segment {
+        Segment::Line(self.end.clone(), self.start.clone())
+    }
+
+    fn closed_distance_squared(&self, p: &Vec3) -> f64 {
+        self.distance_squared(p)
+            .min(self.closing_segment().distance_squared(p))
+    }
+
+    /// Winding number of the closed curve around `p`, positive for counterclockwise
+    /// contours.
+    fn winding(&self, p: &Vec3) -> i32 {
+        if p.y < self.bounds.min.0.y || p.y > self.bounds.max.0.y || p.x > self.bounds.max.0.x {
+            return 0;
+        }
+        self.segments.iter().map(|s| s.winding(p)).sum::<i32>() + self.closing_segment().winding(p)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Line(Vec3, Vec3),
+    Quadratic(Vec3, Vec3, Vec3),
+    Cubic(Vec3, Vec3, Vec3, Vec3),
+}
+
+impl Segment {
+    fn point(&self, t: f64) -> Vec3 {
+        let s = 1.0 - t;
+        match self {
+            Segment::Line(a, b) => a * s + b * t,
+            Segment::Quadratic(a, b, c) => a * (s * s) + b * (2.0 * s * t) + c * (t * t),
+            Segment::Cubic(a, b, c, d) => {
+                a * (s * s * s) + b * (3.0 * s * s * t) + c * (3.0 * s * t * t) + d * (t * t * t)
+            }
+        }
+    }
+
+    fn distance_squared(&self, p: &Vec3) -> f64 {
+        match self {
+            Segment::Line(a, b) => segment_distance_squared(&(b - a), &(p - a)),
+            Segment::Quadratic(a, b, c) => quadratic_distance_squared(a, b, c, p),
+            Segment::Cubic(a, b, c, d) => cubic_distance_squared(a, b, c, d, p),
+        }
+    }
+
+    /// Parameters in `(0, 1)` where the y coordinate turns around, in increasing order.
+    fn y_extrema(&self) -> ([f64; 2], usize) {
+        let mut extrema = [0.0; 2];
+        let mut count = 0;
+        let mut add = |t: f64| {
+            if t > 0.0 && t < 1.0 {
+                extrema[count] = t;
+                count += 1;
+            }
+        };
+        match self {
+            Segment::Line(..) => {}
+            Segment::Quadratic(a, b, c) => {
+                let denominator = a.y - 2.0 * b.y + c.y;
+                if denominator != 0.0 {
+                    add((a.y - b.y) / denominator);
+                }
+            }
+            Segment::Cubic(a, b, c, d) => {
+                // Derivative of the y coordinate, divided by 3
+                let qa = d.y - 3.0 * c.y + 3.0 * b.y - a.y;
+                let qb = 2.0 * (c.y - 2.0 * b.y + a.y);
+                let qc = b.y - a.y;
+                if qa.abs() <= 1e-12 * (qb.abs() + qc.abs()) {
+                    if qb != 0.0 {
+                        add(-qc / qb);
+                    }
+                } else {
+                    let discriminant = qb * qb - 4.0 * qa * qc;
+                    if discriminant >= 0.0 {
+                        let root = discriminant.sqrt();
+                        let (t0, t1) = ((-qb - root) / (2.0 * qa), (-qb + root) / (2.0 * qa));
+                        add(t0.min(t1));
+                        add(t0.max(t1));
+                    }
+                }
+            }
+        }
+        (extrema, count)
+    }
+
+    /// Signed number of times the segment crosses the ray from `p` towards +x, counting
+    /// upward crossings as positive.
+    fn winding(&self, p: &Vec3) -> i32 {
+        if let Segment::Line(a, b) = self {
+            if (a.y <= p.y) == (b.y <= p.y) {
+                return 0;
+            }
+            let x = a.x + (p.y - a.y) / (b.y - a.y) * (b.x - a.x);
+            return if x <= p.x {
+                0
+            } else if b.y > a.y {
+                1
+            } else {
+                -1
+            };
+        }
+
+        // Split into pieces where y is monotonic, which each cross at most once
+        let (extrema, count) = self.y_extrema();
+        let mut winding = 0;
+        let mut t0 = 0.0;
+        let mut y0 = self.point(0.0).y;
+        for &t1 in extrema[..count].iter().chain([1.0].iter()) {
+            let y1 = self.point(t1).y;
+            let start_below = y0 <= p.y;
+            if start_below != (y1 <= p.y) {
+                let (mut lo, mut hi) = (t0, t1);
+                for _ in 0..BISECTIONS {
+                    let mid = 0.5 * (lo + hi);
+                    if (self.point(mid).y <= p.y) == start_below {
+                        lo = mid;
+                    } else {
+                        hi = mid;
+                    }
+                }
+                if self.point(0.5 * (lo + hi)).x > p.x {
+                    winding += if y1 > y0 { 1 } else { -1 };
+                }
+            }
+            t0 = t1;
+            y0 = y1;
+        }
+        winding
+    }
+}
+
+/// Squared distance from `p` to the quadratic Bezier segment with control points `a`, `b`
+/// and `c`, by solving the cubic for the closest point in closed form.
+fn quadratic_distance_squared(a: &Vec3, b: &Vec3, c: &Vec3, p: &Vec3) -> f64 {
+    let ab = b - a;
+    let curvature = a - b * 2.0 + c;
+    // With the control point halfway, the segment is a straight line
+    if curvature.length_squared() <= 1e-12 * (c - a).length_squared() {
+        return segment_distance_squared(&(c - a), &(p - a));
+    }
+    let tangent = &ab * 2.0;
+    let start = a - p;
+    let at = |t: f64| (&start + (&tangent + &curvature * t) * t).length_squared();
+
+    let kk = 1.0 / curvature.length_squared();
+    let kx = kk * ab.dot(&curvature);
+    let ky = kk * (2.0 * ab.length_squared() + start.dot(&curvature)) / 3.0;
+    let kz = kk * start.dot(&ab);
+    let p = ky - kx * kx;
+    let q = kx * (2.0 * kx * kx - 3.0 * ky) + kz;
+    let h = q * q + 4.0 * p * p * p;
+    if h >= 0.0 {
+        // One real root
+        let h = h.sqrt();
+        let t = ((0.5 * (h - q)).cbrt() + (0.5 * (-h - q)).cbrt() - kx).clamp(0.0, 1.0);
+        at(t)
+    } else {
+        // Three real roots, of which the middle one is never the closest
+        let z = (-p).sqrt();
+        let v = (q / (2.0 * p * z)).clamp(-1.0, 1.0).acos() / 3.0;
+        let (m, n) = (v.cos(), v.sin() * 3f64.sqrt());
+        let t0 = (2.0 * m * z - kx).clamp(0.0, 1.0);
+        let t1 = ((-n - m) * z - kx).clamp(0.0, 1.0);
+        at(t0).min(at(t1))
+    }
+}
+
+/// Squared distance from `p` to the cubic Bezier segment with control points `a`, `b`,
+/// `c` and `d`.
+///
+/// The closest point is an end point or a root of a quintic, which has no closed form.
+/// Its roots are isolated by subdividing the quintic in Bernstein form, whose control
+/// values change sign at least as often as the polynomial itself, and then refined by
+/// bisection. Unlike stepping along the segment this can't miss roots that are close
+/// together.
+fn cubic_distance_squared(a: &Vec3, b: &Vec3, c: &Vec3, d: &Vec3, p: &Vec3) -> f64 {
+    // Power basis: offset(t) = c3 t^3 + c2 t^2 + c1 t + c0
+    let c0 = a - p;
+    let c1 = (b - a) * 3.0;
+    let c2 = (a - b * 2.0 + c) * 3.0;
+    let c3 = d - c * 3.0 + b * 3.0 - a;
+    let offset = |t: f64| ((&c3 * t + &c2) * t + &c1) * t + &c0;
+
+    // Half the derivative of the squared distance, offset(t) . offset'(t), in powers of t
+    let slope = [
+        c0.dot(&c1),
+        c1.dot(&c1) + 2.0 * c0.dot(&c2),
+        3.0 * c1.dot(&c2) + 3.0 * c0.dot(&c3),
+        4.0 * c1.dot(&c3) + 2.0 * c2.dot(&c2),
+        5.0 * c2.dot(&c3),
+        3.0 * c3.dot(&c3),
+    ];
+
+    let mut best = offset(0.0)
+        .length_squared()
+        .min(offset(1.0).length_squared());
+    for_each_root(&slope, |t| {
+        best = best.min(offset(t).length_squared());
+    });
+    best
+}
+
+/// Degree of the polynomials handled by [for_each_root].
+const QUINTIC: usize = 5;
+
+/// Subdivision depth after which an interval that may still hold several roots is
+/// reported by its midpoint. It is then less than `2^-40` wide.
+const MAX_SUBDIVISIONS: usize = 40;
+
+/// Calls `f` with every root in `[0, 1]` of the quintic with power basis coefficients
+/// `power`, lowest degree first. Double roots may be reported once or not at all, which
+/// doesn't matter for finding minima as the endpoints of the enclosing interval are close.
+fn for_each_root(power: &[f64; QUINTIC + 1], mut f: impl FnMut(f64)) {
+    let evaluate = |t: f64| power.iter().rev().fold(0.0, |acc, k| acc * t + k);
+
+    // Bernstein coefficients: b_k = sum over i <= k of C(k, i) / C(n, i) a_i
+    let mut bernstein = [0.0; QUINTIC + 1];
+    for (k, b) in bernstein.iter_mut().enumerate() {
+        *b = (0..=k)
+            .map(|i| binomial(k, i) / binomial(QUINTIC, i) * power[i])
+            .sum();
+    }
+
+    // Depth-first, so the stack never holds more than one interval per level
+    let mut stack = [(0.0, 1.0, [0.0; QUINTIC + 1]); MAX_SUBDIVISIONS + 2];
+    stack[0] = (0.0, 1.0, bernstein);
+    let mut len = 1;
+    while len > 0 {
+        len -= 1;
+        let (t0, t1, coefficients) = stack[len];
+        let changes = sign_changes(&coefficients);
+        if changes == 0 {
+            continue;
+        }
+        if changes == 1 {
+            // Exactly one root, and the end values have opposite signs
+            let start_negative = coefficients[0] < 0.0 || coefficients[QUINTIC] > 0.0;
+            let (mut lo, mut hi) = (t0, t1);
+            for _ in 0..BISECTIONS {
+                let mid = 0.5 * (lo + hi);
+                if (evaluate(mid) < 0.0) == start_negative {
+                    lo = mid;
+                } else {
+                    hi = mid;
+                }
+            }
+            f(0.5 * (lo + hi));
+            continue;
+        }
+        let mid = 0.5 * (t0 + t1);
+        if len >= MAX_SUBDIVISIONS {
+            f(mid);
+            continue;
+        }
+        let (left, right) = subdivide(&coefficients);
+        stack[len] = (mid, t1, right);
+        stack[len + 1] = (t0, mid, left);
+        len += 2;
+    }
+}
+
+fn binomial(n: usize, k: usize) -> f64 {
+    (0..k).fold(1.0, |acc, i| acc * (n - i) as f64 / (i + 1) as f64)
+}
+
+/// Number of sign changes between consecutive nonzero coefficients.
+fn sign_changes(coefficients: &[f64]) -> usize {
+    let mut changes = 0;
+    let mut previous = 0.0;
+    for &c in coefficients.iter().filter(|&&c| c != 0.0) {
+        if previous * c < 0.0 {
+            changes += 1;
+        }
+        previous = c;
+    }
+    changes
+}
+
+/// Splits a polynomial in Bernstein form at the middle of its interval with de Casteljau's
+/// algorithm.
+fn subdivide(coefficients: &[f64; QUINTIC + 1]) -> ([f64; QUINTIC + 1], [f64; QUINTIC + 1]) {
+    let mut work = *coefficients;
+    let mut left = [0.0; QUINTIC + 1];
+    let mut right = [0.0; QUINTIC + 1];
+    for level in 0..=QUINTIC {
+        left[level] = work[0];
+        right[QUINTIC - level] = work[QUINTIC - level];
+        for i in 0..QUINTIC - level {
+            work[i] = 0.5 * (work[i] + work[i + 1]);
+        }
+    }
+    (left, right)
+}
+
+/// How overlapping contours of a [Path] decide what is inside.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FillRule {
+    /// Inside where the contours wind around the point an odd number of times.
+    EvenOdd,
+    /// Inside where the contours wind around the point at all, counting counterclockwise
+    /// contours as positive and clockwise ones as negative.
+    NonZero,
+}
+
+/// Shape made of one or more closed contours, for example an outline with holes.
+///
+/// Each contour is closed by a straight line from its end back to its start. Distances
+/// are exact; whether a point is inside is decided by the [FillRule] from the winding
+/// number of all contours together.
+#[derive(Debug, Clone)]
+pub struct Path {
+    contours: Vec<Curve<Vec2>>,
+    fill_rule: FillRule,
+}
+
+impl Path {
+    pub fn new(fill_rule: FillRule) -> Self {
+        Self {
+            contours: Vec::new(),
+            fill_rule,
+        }
+    }
+
+    pub fn push(&mut self, contour: Curve<Vec2>) {
+        self.contours.push(contour);
+    }
+
+    pub fn with(mut self, contour: Curve<Vec2>) -> Self {
+        self.push(contour);
+        self
+    }
+}
+
+impl Sdf2d for Path {
+    fn distance_at(&self, p: Vec2) -> f64 {
+        let p = p.to_vec3();
+        let mut distance_squared = f64::INFINITY;
+        let mut winding = 0;
+        for contour in self.contours.iter() {
+            distance_squared = distance_squared.min(contour.closed_distance_squared(&p));
+            winding += contour.winding(&p);
+        }
+        let inside = match self.fill_rule {
+            FillRule::EvenOdd => winding % 2 != 0,
+            FillRule::NonZero => winding != 0,
+        };
+        let distance = distance_squared.sqrt();
+        if inside {
+            -distance
+        } else {
+            distance
+        }
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.contours
+            .iter()
+            .map(|c| c.bounds.clone())
+            .reduce(|a, b| a.union(&b))
+    }
+}
+
+/// All points within `radius` of a curve, for pipes and wires. The ends are rounded.
+/// Exact.
+#[derive(Debug, Clone)]
+pub struct Tube {
+    curve: Curve<Point3>,
+    radius: f64,
+}
+
+impl Tube {
+    pub fn new(curve: Curve<Point3>, radius: f64) -> Self {
+        Self { curve, radius }
+    }
+}
+
+impl Sdf for Tube {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        (self.curve.distance_squared(&p.0).sqrt() - self.radius, None)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(self.curve.bounds.expand(self.radius))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::ApproxEq;
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::scene::scenemap::sdf::primitives::Capsule;
+    use crate::scene::scenemap::sdf::sdf2d::{Circle, Rectangle};
+    use crate::test_constants::MARGIN;
+
+    /// Smallest squared distance to `samples + 1` evenly spaced points along `segment`.
+    /// Samples the segment and refines every sampled local minimum by ternary search.
+    fn sampled_distance_squared(segment: &Segment, p: &Vec3, samples: usize) -> f64 {
+        let step = 1.0 / samples as f64;
+        let at = |t: f64| (segment.point(t.clamp(0.0, 1.0)) - p).length_squared();
+        let sampled: Vec<f64> = (0..=samples).map(|i| at(i as f64 * step)).collect();
+        (0..=samples)
+            .filter(|&i| {
+                (i == 0 || sampled[i - 1] >= sampled[i])
+                    && (i == samples || sampled[i + 1] >= sampled[i])
+            })
+            .map(|i| {
+                let (mut lo, mut hi) = ((i as f64 - 1.0) * step, (i as f64 + 1.0) * step);
+                for _ in 0..100 {
+                    let (m0, m1) = (lo + (hi - lo) / 3.0, hi - (hi - lo) / 3.0);
+                    if at(m0) < at(m1) {
+                        hi = m1;
+                    } else {
+                        lo = m0;
+                    }
+                }
+                at(0.5 * (lo + hi)).min(sampled[i])
+            })
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    fn square(half: f64, counterclockwise: bool) -> Curve<Vec2> {
+        let corners = [(-half, -half), (half, -half), (half, half), (-half, half)];
+        let mut corners: Vec<Vec2> = corners.iter().map(|&(x, y)| Vec2::new(x, y)).collect();
+        if !counterclockwise {
+            corners.reverse();
+        }
+        corners[1..]
+            .iter()
+            .fold(Curve::new(corners[0]), |c, &v| c.line_to(v))
+    }
+
+    /// Circle of radius 1 made of four cubic arcs.
+    fn cubic_circle() -> Curve<Vec2> {
+        let k = 0.551_915_024_494;
+        Curve::new(Vec2::new(1.0, 0.0))
+            .cubic_to(Vec2::new(1.0, k), Vec2::new(k, 1.0), Vec2::new(0.0, 1.0))
+            .cubic_to(Vec2::new(-k, 1.0), Vec2::new(-1.0, k), Vec2::new(-1.0, 0.0))
+            .cubic_to(
+                Vec2::new(-1.0, -k),
+                Vec2::new(-k, -1.0),
+                Vec2::new(0.0, -1.0),
+            )
+            .cubic_to(Vec2::new(k, -1.0), Vec2::new(1.0, -k), Vec2::new(1.0, 0.0))
+    }
+
+    #[test]
+    fn concave_polygon_is_exact() {
+        // L shape
+        let polygon = Polygon::new(vec![
+            Vec2::new(0.0, 0.0),
+            Vec2::new(2.0, 0.0),
+            Vec2::new(2.0, 1.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(1.0, 2.0),
+            Vec2::new(0.0, 2.0),
+        ]);
+        let d = |x, y| polygon.distance_at(Vec2::new(x, y));
+        assert!(d(0.5, 0.5).approx_eq(-0.5, MARGIN));
+        assert!(d(1.5, 1.5).approx_eq(0.5, MARGIN));
+        assert!(d(2.0, 2.0).approx_eq(1.0, MARGIN));
+        assert!(d(1.5, 0.8).approx_eq(-0.2, MARGIN));
+        assert!(d(-1.0, 1.0).approx_eq(1.0, MARGIN));
+    }
+
+    #[test]
+    fn fill_rules_decide_holes() {
+        let p = Vec2::ZERO;
+        let same = |rule| {
+            Path::new(rule)
+                .with(square(2.0, true))
+                .with(square(1.0, true))
+                .distance_at(p)
+        };
+        assert!(same(FillRule::NonZero).approx_eq(-1.0, MARGIN));
+        assert!(same(FillRule::EvenOdd).approx_eq(1.0, MARGIN));
+
+        let opposite = |rule| {
+            Path::new(rule)
+                .with(square(2.0, true))
+                .with(square(1.0, false))
+                .distance_at(p)
+        };
+        assert!(opposite(FillRule::NonZero).approx_eq(1.0, MARGIN));
+        assert!(opposite(FillRule::EvenOdd).approx_eq(1.0, MARGIN));
+
+        // Between the contours both rules agree
+        let ring = Path::new(FillRule::EvenOdd)
+            .with(square(2.0, true))
+            .with(square(1.0, true));
+        assert!(ring
+            .distance_at(Vec2::new(1.5, 0.0))
+            .approx_eq(-0.5, MARGIN));
+    }
+
+    #[test]
+    fn cubic_circle_is_close_to_circle() {
+        let path = Path::new(FillRule::NonZero).with(cubic_circle());
+        let circle = Circle::new(1.0, Vec2::ZERO);
+        for &(x, y) in [
+            (0.0, 0.0),
+            (0.3, -0.5),
+            (1.5, 0.2),
+            (-0.7, 0.7),
+            (0.0, -3.0),
+        ]
+        .iter()
+        {
+            let p = Vec2::new(x, y);
+            assert!((path.distance_at(p) - circle.distance_at(p)).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn straight_quadratic_is_line() {
+        let curve = Curve::new(Point3::new(0.0, 0.0, 0.0))
+            .quadratic_to(Point3::new(1.0, 0.0, 0.0), Point3::new(2.0, 0.0, 0.0));
+        let tube = Tube::new(curve, 0.5);
+        let capsule = Capsule::new(Point3::ORIGIN, Point3::new(2.0, 0.0, 0.0), 0.5);
+        for p in [Point3::new(1.0, 1.0, 0.0), Point3::new(-1.0, 0.0, 1.0)].iter() {
+            assert!(tube.value_at(p).0.approx_eq(capsule.value_at(p).0, MARGIN));
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn polygon_path_and_rectangle_agree(x in -3.0..3.0, y in -3.0..3.0) {
+            let p = Vec2::new(x, y);
+            let rectangle = Rectangle::new_half_extents(Vec2::new(1.0, 1.0), Vec2::ZERO);
+            let polygon = Polygon::new(vec![
+                Vec2::new(-1.0, -1.0),
+                Vec2::new(1.0, -1.0),
+                Vec2::new(1.0, 1.0),
+                Vec2::new(-1.0, 1.0),
+            ]);
+            let path = Path::new(FillRule::NonZero).with(square(1.0, false));
+            let expected = rectangle.distance_at(p);
+            prop_assert!(polygon.distance_at(p).approx_eq(expected, MARGIN));
+            prop_assert!(path.distance_at(p).approx_eq(expected, MARGIN));
+        }
+
+        #[test]
+        fn bezier_distances_match_sampling(
+            x in -2.0..2.0, y in -2.0..2.0, z in -2.0..2.0,
+            bx in -2.0..2.0, by in -2.0..2.0, cx in -2.0..2.0, cy in -2.0..2.0,
+        ) {
+            let p = Vec3::new(x, y, z);
+            let (a, b, c, d) = (
+                Vec3::new(-1.0, 0.0, 0.0),
+                Vec3::new(bx, by, 1.0),
+                Vec3::new(cx, cy, -1.0),
+                Vec3::new(1.0, 0.5, 0.0),
+            );
+            for segment in [
+                Segment::Quadratic(a.clone(), b.clone(), d.clone()),
+                Segment::Cubic(a, b, c, d),
+            ].iter() {
+                let exact = segment.distance_squared(&p).sqrt();
+                let sampled = sampled_distance_squared(segment, &p, 2_000).sqrt();
+                // Sampling can only find points that are at least as far
+                prop_assert!(exact <= sampled + 1e-9);
+                prop_assert!(exact >= sampled - 1e-6);
+            }
+        }
+
+        #[test]
+        fn closed_bezier_sign_matches_winding(x in -2.0..2.0, y in -2.0..2.0) {
+            let contour = Curve::new(Vec2::new(-1.0, 0.0))
+                .quadratic_to(Vec2::new(0.0, -2.0), Vec2::new(1.0, 0.0))
+                .cubic_to(Vec2::new(1.0, 2.0), Vec2::new(-2.0, 0.5), Vec2::new(-0.5, 1.0));
+            let path = Path::new(FillRule::NonZero).with(contour);
+            let p = Vec2::new(x, y);
+            let d = path.distance_at(p);
+            // Moving by the distance can't cross the outline, so the sign must be stable
+            let nearby = Vec2::new(x + d.abs() * 0.5, y);
+            prop_assert!(d == 0.0 || path.distance_at(nearby).signum() == d.signum());
+            let bounds = path.bounds().unwrap();
+            if bounds.distance_to(&Point3::new(x, y, 0.0)) > 0.0 {
+                prop_assert!(d > 0.0);
+            }
+        }
+    }
+}
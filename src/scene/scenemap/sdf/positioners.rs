@@ -1,7 +1,9 @@
-use crate::primitives::{Quaternion, UnitVec3};
-use crate::scene::scenemap::material::MaterialIndex;
-use crate::scene::scenemap::sdf::Sdf;
-use crate::{Point3, Vec3};
+use std::f64::consts::TAU;
+
+use crate::primitives::{Matrix4, Quaternion, UnitVec3};
+use crate::scene::scenemap::material::{MaterialBlend, MaterialIndex};
+use crate::scene::scenemap::sdf::{bounded, Sdf};
+use crate::{Aabb, Point3, Vec3};
 
 #[derive(Debug, Clone)]
 pub struct Translate<A> {
@@ -16,9 +18,13 @@ impl<A> Translate<A> {
 }
 
 impl<A: Sdf> Sdf for Translate<A> {
-    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialIndex>) {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
         self.a.value_at(&(p.as_ref() - &self.v).into())
     }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.a.bounds().map(|b| b.translate(&self.v))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -34,10 +40,54 @@ impl<A> ScaleUniform<A> {
 }
 
 impl<A: Sdf> Sdf for ScaleUniform<A> {
-    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialIndex>) {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
         let (f, m) = self.a.value_at(&Point3(p.as_ref() / self.f));
         (f * self.f, m)
     }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.a.bounds().map(|b| b.scale(self.f))
+    }
+}
+
+/// Scales an SDF by a separate factor along each axis.
+///
+/// Stretching distorts distances, so the result is multiplied by the smallest factor,
+/// which never overestimates the distance to the scaled surface.
+#[derive(Debug, Clone)]
+pub struct Scale<A> {
+    a: A,
+    f: Vec3,
+}
+
+impl<A> Scale<A> {
+    /// Smallest factor along any axis, to keep flattened SDFs from dividing by zero.
+    const MIN_FACTOR: f64 = 1e-6;
+
+    /// Factors smaller than `1e-6` are clamped to it, keeping their sign.
+    pub fn new(a: A, f: Vec3) -> Self {
+        let clamp = |v: f64| v.signum() * v.abs().max(Self::MIN_FACTOR);
+        Self {
+            a,
+            f: Vec3::new(clamp(f.x), clamp(f.y), clamp(f.z)),
+        }
+    }
+}
+
+impl<A: Sdf> Sdf for Scale<A> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let f = &self.f;
+        let (d, m) = self
+            .a
+            .value_at(&Point3::new(p.0.x / f.x, p.0.y / f.y, p.0.z / f.z));
+        (d * f.abs().min_component(), m)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.a
+            .bounds()
+            .map(|b| Aabb::new(Point3(&b.min.0 * &self.f), Point3(&b.max.0 * &self.f)))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -60,7 +110,7 @@ impl<A> Rotate<A> {
 }
 
 impl<A: Sdf> Sdf for Rotate<A> {
-    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialIndex>) {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
         let v = p.as_ref();
         let q = &self.q;
         let q_inv = q.conjugate();
@@ -68,4 +118,682 @@ impl<A: Sdf> Sdf for Rotate<A> {
         let p_new = Point3((q * qv * q_inv).vec().clone());
         self.a.value_at(&p_new)
     }
+
+    /// Box around the rotated corners of the inner bounds. Infinite bounds can't be
+    /// rotated, so those become unbounded.
+    fn bounds(&self) -> Option<Aabb> {
+        let bounds = self.a.bounds().filter(Aabb::is_finite)?;
+        // `q` holds the inverse rotation, so the conjugate rotates the object into place
+        let q = self.q.conjugate();
+        let q_inv = &self.q;
+        Aabb::from_points(bounds.corners().iter().map(|c| {
+            let qv = Quaternion::new(0.0, c.0.clone());
+            Point3((&q * qv * q_inv).vec().clone())
+        }))
+    }
+}
+
+/// Applies an affine transformation, given as a matrix, to an SDF.
+///
+/// Only the precomputed inverse is needed to evaluate the SDF, so a whole stack of
+/// translations, rotations, scales and shears costs a single matrix-vector product.
+/// Scales and shears distort distances, so the result is multiplied by the smallest
+/// stretch of the matrix, which never overestimates.
+#[derive(Debug, Clone)]
+pub struct Transform<A> {
+    a: A,
+    forward: Matrix4,
+    inverse: Matrix4,
+    stretch: f64,
+}
+
+impl<A> Transform<A> {
+    /// Returns [None] if `transform` can't be inverted.
+    pub fn new(a: A, transform: Matrix4) -> Option<Self> {
+        let inverse = transform.inverse()?;
+        Some(Self {
+            a,
+            stretch: transform.min_stretch(),
+            forward: transform,
+            inverse,
+        })
+    }
+}
+
+impl<A: Sdf> Sdf for Transform<A> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let (d, m) = self.a.value_at(&self.inverse.transform_point(p));
+        (d * self.stretch, m)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let bounds = self.a.bounds().filter(Aabb::is_finite)?;
+        Aabb::from_points(
+            bounds
+                .corners()
+                .iter()
+                .map(|c| self.forward.transform_point(c)),
+        )
+    }
+}
+
+/// Index of a copy made by one of the repetition positioners, per axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct CellIndex {
+    pub x: i64,
+    pub y: i64,
+    pub z: i64,
+}
+
+impl CellIndex {
+    /// Cell containing `p` for the given period. Axes with a period of zero are not
+    /// repeated and always have index zero.
+    fn containing(p: &Point3, period: &Vec3) -> Self {
+        let axis = |v: f64, period: f64| {
+            if period > 0.0 {
+                (v / period).round() as i64
+            } else {
+                0
+            }
+        };
+        Self {
+            x: axis(p.0.x, period.x),
+            y: axis(p.0.y, period.y),
+            z: axis(p.0.z, period.z),
+        }
+    }
+
+    /// Position of the center of this cell.
+    fn offset(&self, period: &Vec3) -> Vec3 {
+        period * Vec3::new(self.x as f64, self.y as f64, self.z as f64)
+    }
+}
+
+/// Positioners that repeat an SDF and can tell which copy a point belongs to.
+pub trait CellIndexed {
+    fn cell_at(&self, p: &Point3) -> CellIndex;
+}
+
+/// Makes `bounds` infinite along the repeated axes, or [None] if that leaves no finite side.
+fn unbounded_along(bounds: Aabb, period: &Vec3) -> Option<Aabb> {
+    let axis = |min: f64, max: f64, period: f64| {
+        if period > 0.0 {
+            (f64::NEG_INFINITY, f64::INFINITY)
+        } else {
+            (min, max)
+        }
+    };
+    let (min, max) = (&bounds.min.0, &bounds.max.0);
+    let x = axis(min.x, max.x, period.x);
+    let y = axis(min.y, max.y, period.y);
+    let z = axis(min.z, max.z, period.z);
+    bounded(Aabb {
+        min: Point3::new(x.0, y.0, z.0),
+        max: Point3::new(x.1, y.1, z.1),
+    })
+}
+
+/// Repeats an SDF infinitely, with one copy centered on every multiple of `period`.
+///
+/// Axes with a period of zero are not repeated. The SDF should fit within a single cell,
+/// otherwise neighbouring copies get cut off.
+#[derive(Debug, Clone)]
+pub struct Repeat<A> {
+    a: A,
+    period: Vec3,
+}
+
+impl<A> Repeat<A> {
+    pub fn new(a: A, period: Vec3) -> Self {
+        Self { a, period }
+    }
+}
+
+impl<A> CellIndexed for Repeat<A> {
+    fn cell_at(&self, p: &Point3) -> CellIndex {
+        CellIndex::containing(p, &self.period)
+    }
+}
+
+impl<A: Sdf> Sdf for Repeat<A> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let offset = self.cell_at(p).offset(&self.period);
+        self.a.value_at(&(p - &offset))
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.a
+            .bounds()
+            .and_then(|b| unbounded_along(b, &self.period))
+    }
+}
+
+/// Repeats an SDF `count` times along each axis, with copies centered on `0`,
+/// `period`, ..., `(count - 1) * period`.
+///
+/// Axes with a count of at most one are not repeated.
+#[derive(Debug, Clone)]
+pub struct RepeatLimited<A> {
+    a: A,
+    period: Vec3,
+    count: [usize; 3],
+}
+
+impl<A> RepeatLimited<A> {
+    pub fn new(a: A, period: Vec3, count: [usize; 3]) -> Self {
+        Self { a, period, count }
+    }
+
+    fn last_cell(&self) -> CellIndex {
+        let last = |n: usize| n.max(1) as i64 - 1;
+        CellIndex {
+            x: last(self.count[0]),
+            y: last(self.count[1]),
+            z: last(self.count[2]),
+        }
+    }
+}
+
+impl<A> CellIndexed for RepeatLimited<A> {
+    fn cell_at(&self, p: &Point3) -> CellIndex {
+        let cell = CellIndex::containing(p, &self.period);
+        let last = self.last_cell();
+        CellIndex {
+            x: cell.x.clamp(0, last.x),
+            y: cell.y.clamp(0, last.y),
+            z: cell.z.clamp(0, last.z),
+        }
+    }
+}
+
+impl<A: Sdf> Sdf for RepeatLimited<A> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let offset = self.cell_at(p).offset(&self.period);
+        self.a.value_at(&(p - &offset))
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let bounds = self.a.bounds()?;
+        let last = bounds.translate(&self.last_cell().offset(&self.period));
+        Some(bounds.union(&last))
+    }
+}
+
+/// Like [Repeat], but every other copy is mirrored, so the copies meet seamlessly even if
+/// the SDF doesn't fit within a single cell.
+#[derive(Debug, Clone)]
+pub struct RepeatMirrored<A> {
+    a: A,
+    period: Vec3,
+}
+
+impl<A> RepeatMirrored<A> {
+    pub fn new(a: A, period: Vec3) -> Self {
+        Self { a, period }
+    }
+}
+
+impl<A> CellIndexed for RepeatMirrored<A> {
+    fn cell_at(&self, p: &Point3) -> CellIndex {
+        CellIndex::containing(p, &self.period)
+    }
+}
+
+impl<A: Sdf> Sdf for RepeatMirrored<A> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let cell = self.cell_at(p);
+        let local = p.as_ref() - cell.offset(&self.period);
+        let mirror = |v: f64, i: i64| if i % 2 == 0 { v } else { -v };
+        self.a.value_at(&Point3::new(
+            mirror(local.x, cell.x),
+            mirror(local.y, cell.y),
+            mirror(local.z, cell.z),
+        ))
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.a
+            .bounds()
+            .and_then(|b| unbounded_along(b, &self.period))
+    }
+}
+
+/// Gives every copy made by a repetition positioner its own material, chosen by `f` from
+/// the cell index. If `f` returns [None] the material of the repeated SDF is kept.
+pub struct WithCellMaterial<R, F> {
+    r: R,
+    f: F,
+}
+
+impl<R, F> WithCellMaterial<R, F>
+where
+    R: Sdf + CellIndexed,
+    F: Fn(CellIndex) -> Option<MaterialIndex> + Send + Sync,
+{
+    pub fn new(r: R, f: F) -> Self {
+        Self { r, f }
+    }
+}
+
+impl<R, F> Sdf for WithCellMaterial<R, F>
+where
+    R: Sdf + CellIndexed,
+    F: Fn(CellIndex) -> Option<MaterialIndex> + Send + Sync,
+{
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let (d, m) = self.r.value_at(p);
+        match (self.f)(self.r.cell_at(p)) {
+            Some(cell_material) => (d, Some(cell_material.into())),
+            None => (d, m),
+        }
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.r.bounds()
+    }
+}
+
+/// Coordinate axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    /// Splits `v` into its component along the axis and its coordinates in the plane
+    /// perpendicular to it, with the plane axes in right-handed order.
+    pub(super) fn split(self, v: &Vec3) -> (f64, f64, f64) {
+        match self {
+            Axis::X => (v.x, v.y, v.z),
+            Axis::Y => (v.y, v.z, v.x),
+            Axis::Z => (v.z, v.x, v.y),
+        }
+    }
+
+    /// Inverse of [Self::split].
+    pub(super) fn join(self, along: f64, u: f64, v: f64) -> Vec3 {
+        match self {
+            Axis::X => Vec3::new(along, u, v),
+            Axis::Y => Vec3::new(v, along, u),
+            Axis::Z => Vec3::new(u, v, along),
+        }
+    }
+}
+
+/// Repeats an SDF `sectors` times around an axis through the origin.
+///
+/// Space is folded into a wedge of `360 / sectors` degrees centered on the first plane
+/// axis after `axis` (`y` for [Axis::X], `z` for [Axis::Y] and `x` for [Axis::Z]), so the
+/// SDF should be modelled inside that wedge. Sectors are numbered counterclockwise from
+/// there, and are reported by [CellIndexed::cell_at] as the cell index along `axis`.
+#[derive(Debug, Clone)]
+pub struct RepeatPolar<A> {
+    a: A,
+    axis: Axis,
+    sectors: usize,
+}
+
+impl<A> RepeatPolar<A> {
+    pub fn new(a: A, axis: Axis, sectors: usize) -> Self {
+        Self {
+            a,
+            axis,
+            sectors: sectors.max(1),
+        }
+    }
+
+    fn sector_angle(&self) -> f64 {
+        TAU / self.sectors as f64
+    }
+
+    /// Sector containing `p`, between 0 and `sectors - 1`.
+    pub fn sector_at(&self, p: &Point3) -> usize {
+        let (_, u, v) = self.axis.split(&p.0);
+        let sector = (v.atan2(u) / self.sector_angle()).round() as i64;
+        sector.rem_euclid(self.sectors as i64) as usize
+    }
+}
+
+impl<A> CellIndexed for RepeatPolar<A> {
+    fn cell_at(&self, p: &Point3) -> CellIndex {
+        let sector = self.sector_at(p) as i64;
+        match self.axis {
+            Axis::X => CellIndex {
+                x: sector,
+                ..CellIndex::default()
+            },
+            Axis::Y => CellIndex {
+                y: sector,
+                ..CellIndex::default()
+            },
+            Axis::Z => CellIndex {
+                z: sector,
+                ..CellIndex::default()
+            },
+        }
+    }
+}
+
+impl<A: Sdf> Sdf for RepeatPolar<A> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let (along, u, v) = self.axis.split(&p.0);
+        let sector_angle = self.sector_angle();
+        let angle = v.atan2(u);
+        let angle = angle - (angle / sector_angle).round() * sector_angle;
+        let radius = u.hypot(v);
+        let (sin, cos) = angle.sin_cos();
+        self.a
+            .value_at(&Point3(self.axis.join(along, radius * cos, radius * sin)))
+    }
+
+    /// A box around the cylinder swept by the inner bounds.
+    fn bounds(&self) -> Option<Aabb> {
+        let bounds = self.a.bounds().filter(Aabb::is_finite)?;
+        let corners = bounds.corners();
+        let radius = corners
+            .iter()
+            .map(|c| {
+                let (_, u, v) = self.axis.split(&c.0);
+                u.hypot(v)
+            })
+            .fold(0.0, f64::max);
+        let (min, ..) = self.axis.split(&bounds.min.0);
+        let (max, ..) = self.axis.split(&bounds.max.0);
+        Some(Aabb::new(
+            Point3(self.axis.join(min, -radius, -radius)),
+            Point3(self.axis.join(max, radius, radius)),
+        ))
+    }
+}
+
+/// Mirrors the part of an SDF in front of a plane onto the back of it.
+///
+/// The plane consists of the points `p` with `p . normal = offset`. Whatever is behind the
+/// plane in the original SDF is discarded.
+#[derive(Debug, Clone)]
+pub struct Mirror<A> {
+    a: A,
+    normal: UnitVec3,
+    offset: f64,
+}
+
+impl<A> Mirror<A> {
+    pub fn new(a: A, normal: UnitVec3, offset: f64) -> Self {
+        Self { a, normal, offset }
+    }
+
+    /// Signed distance from `p` to the plane, positive in front of it.
+    fn plane_distance(&self, p: &Point3) -> f64 {
+        p.0.dot(self.normal.as_ref()) - self.offset
+    }
+
+    fn reflect(&self, p: &Point3) -> Point3 {
+        let d = self.plane_distance(p);
+        Point3(p.as_ref() - self.normal.as_ref() * (2.0 * d))
+    }
+}
+
+impl<A: Sdf> Sdf for Mirror<A> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        if self.plane_distance(p) < 0.0 {
+            self.a.value_at(&self.reflect(p))
+        } else {
+            self.a.value_at(p)
+        }
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let bounds = self.a.bounds().filter(Aabb::is_finite)?;
+        let corners = bounds.corners();
+        Aabb::from_points(
+            corners
+                .iter()
+                .map(|c| self.reflect(c))
+                .chain(corners.iter().cloned()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::ApproxEq;
+
+    use super::*;
+    use crate::scene::scenemap::material::{Material, MaterialList};
+    use crate::scene::scenemap::sdf::primitives::{Cube, Sphere};
+    use crate::test_constants::MARGIN;
+
+    fn small_sphere() -> Sphere {
+        small_sphere_at(Point3::ORIGIN)
+    }
+
+    fn small_sphere_at(center: Point3) -> Sphere {
+        Sphere::new(0.25, center)
+    }
+
+    #[test]
+    fn scale_never_overestimates() {
+        let sdf = Scale::new(Cube::default(), Vec3::new(3.0, 1.0, 0.5));
+
+        // On the surface and inside
+        assert!(sdf
+            .value_at(&Point3::new(3.0, 0.0, 0.0))
+            .0
+            .approx_eq(0.0, MARGIN));
+        assert!(sdf.value_at(&Point3::new(0.0, 0.0, 0.4)).0 < 0.0);
+        // The true distance is 1, but the slab is only half as thick along z
+        let d = sdf.value_at(&Point3::new(4.0, 0.0, 0.0)).0;
+        assert!(d > 0.0 && d <= 1.0);
+
+        let bounds = sdf.bounds().unwrap();
+        assert!(bounds.max.0.x.approx_eq(3.0, MARGIN));
+        assert!(bounds.min.0.z.approx_eq(-0.5, MARGIN));
+    }
+
+    #[test]
+    fn flattened_scale_has_finite_distances() {
+        let sdf = Scale::new(Sphere::default(), Vec3::new(1.0, 0.0, 1.0));
+        for p in [
+            Point3::ORIGIN,
+            Point3::new(0.0, 2.0, 0.0),
+            Point3::new(3.0, 0.0, 0.0),
+            Point3::new(0.5, -1.0, 0.2),
+        ]
+        .iter()
+        {
+            assert!(sdf.value_at(p).0.is_finite());
+        }
+        assert!(sdf.value_at(&Point3::ORIGIN).0 < 0.0);
+        assert!(sdf.value_at(&Point3::new(0.0, 2.0, 0.0)).0 > 0.0);
+        assert!(sdf.bounds().unwrap().is_finite());
+    }
+
+    #[test]
+    fn transform_matches_nested_positioners() {
+        let axis = || Vec3::new(1.0, -2.0, 0.5).unit();
+        let nested = Translate::new(
+            ScaleUniform::new(Rotate::new(Cube::default(), 0.8, axis()), 1.5),
+            Vec3::new(0.5, 2.0, -1.0),
+        );
+        let matrix = Matrix4::rotation_axis_angle(axis(), 0.8)
+            .then(&Matrix4::scaling(&Vec3::new(1.5, 1.5, 1.5)))
+            .then(&Matrix4::translation(&Vec3::new(0.5, 2.0, -1.0)));
+        let transformed = Transform::new(Cube::default(), matrix).unwrap();
+
+        for p in [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 2.5, -0.3),
+            Point3::new(-3.0, 4.0, 2.0),
+        ]
+        .iter()
+        {
+            assert!(transformed
+                .value_at(p)
+                .0
+                .approx_eq(nested.value_at(p).0, MARGIN));
+        }
+    }
+
+    #[test]
+    fn transform_with_shear_never_overestimates() {
+        let matrix = Matrix4::shearing(1.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+        let sdf = Transform::new(Sphere::default(), matrix).unwrap();
+
+        // Points along the x axis: the sheared sphere still spans -1..1 there
+        for x in [1.5, 2.0, 4.0].iter() {
+            let d = sdf.value_at(&Point3::new(*x, 0.0, 0.0)).0;
+            assert!(d > 0.0 && d <= x - 1.0);
+        }
+        let bounds = sdf.bounds().unwrap();
+        assert!(bounds.max.0.x.approx_eq(2.0, MARGIN));
+        assert!(Transform::new(Sphere::default(), Matrix4::scaling(&Vec3::ZERO)).is_none());
+    }
+
+    #[test]
+    fn repeat_copies_along_chosen_axes() {
+        let sdf = Repeat::new(small_sphere(), Vec3::new(2.0, 0.0, 3.0));
+
+        assert!(sdf
+            .value_at(&Point3::new(-4.0, 0.0, 9.0))
+            .0
+            .approx_eq(-0.25, MARGIN));
+        // Not repeated along y
+        assert!(sdf
+            .value_at(&Point3::new(2.0, 5.0, 0.0))
+            .0
+            .approx_eq(4.75, MARGIN));
+        assert_eq!(
+            sdf.cell_at(&Point3::new(-4.2, 7.0, 8.0)),
+            CellIndex { x: -2, y: 0, z: 3 }
+        );
+    }
+
+    #[test]
+    fn repeat_limited_stops_after_count() {
+        let sdf = RepeatLimited::new(small_sphere(), Vec3::new(1.0, 1.0, 0.0), [3, 2, 1]);
+
+        assert!(sdf
+            .value_at(&Point3::new(2.0, 1.0, 0.0))
+            .0
+            .approx_eq(-0.25, MARGIN));
+        // Past the last copy along x and before the first along y
+        assert!(sdf
+            .value_at(&Point3::new(5.0, -1.0, 0.0))
+            .0
+            .approx_eq(10.0_f64.sqrt() - 0.25, MARGIN));
+
+        let bounds = sdf.bounds().unwrap();
+        assert!(bounds.max.0.x.approx_eq(2.25, MARGIN));
+        assert!(bounds.min.0.y.approx_eq(-0.25, MARGIN));
+        assert!(bounds.max.0.z.approx_eq(0.25, MARGIN));
+    }
+
+    #[test]
+    fn repeat_mirrored_flips_odd_cells() {
+        let sdf = RepeatMirrored::new(
+            Sphere::new(0.25, Point3::new(0.2, 0.0, 0.0)),
+            Vec3::new(1.0, 0.0, 0.0),
+        );
+
+        // Cell 1 is mirrored, so its sphere sits at 0.8 instead of 1.2
+        assert!(sdf
+            .value_at(&Point3::new(0.8, 0.0, 0.0))
+            .0
+            .approx_eq(-0.25, MARGIN));
+        assert!(sdf
+            .value_at(&Point3::new(2.2, 0.0, 0.0))
+            .0
+            .approx_eq(-0.25, MARGIN));
+    }
+
+    #[test]
+    fn polar_repetition_copies_around_axis() {
+        let sdf = RepeatPolar::new(small_sphere_at(Point3::new(1.0, 0.0, 0.0)), Axis::Z, 6);
+
+        let (sin, cos) = (2.0 * TAU / 6.0).sin_cos();
+        assert!(sdf
+            .value_at(&Point3::new(cos, sin, 0.3))
+            .0
+            .approx_eq(0.05, MARGIN));
+        assert!(sdf.value_at(&Point3::ORIGIN).0.approx_eq(0.75, MARGIN));
+        assert_eq!(sdf.sector_at(&Point3::new(cos, sin, 0.0)), 2);
+        assert_eq!(sdf.sector_at(&Point3::new(0.5, -0.01, 0.0)), 0);
+        assert_eq!(sdf.sector_at(&Point3::new(cos, -sin, 0.0)), 4);
+
+        let bounds = sdf.bounds().unwrap();
+        assert!(bounds.min.0.x.approx_eq(-bounds.max.0.x, MARGIN));
+        assert!(bounds.max.0.z.approx_eq(0.25, MARGIN));
+    }
+
+    #[test]
+    fn polar_repetition_composes_with_rotation_and_translation() {
+        // Spokes around the x axis, moved up
+        let sdf = Translate::new(
+            Rotate::new_degrees(
+                RepeatPolar::new(small_sphere_at(Point3::new(1.0, 0.0, 0.0)), Axis::Z, 4),
+                90.0,
+                Vec3::new(0.0, 1.0, 0.0).unit(),
+            ),
+            Vec3::new(0.0, 2.0, 0.0),
+        );
+
+        assert!(sdf
+            .value_at(&Point3::new(0.0, 3.0, 0.0))
+            .0
+            .approx_eq(-0.25, MARGIN));
+        assert!(sdf
+            .value_at(&Point3::new(0.0, 2.0, 1.0))
+            .0
+            .approx_eq(-0.25, MARGIN));
+    }
+
+    #[test]
+    fn mirror_reflects_across_plane() {
+        let normal = Vec3::new(1.0, 1.0, 0.0).unit();
+        let sdf = Mirror::new(small_sphere_at(Point3::new(2.0, 2.0, 0.0)), normal, 0.0);
+
+        assert!(sdf
+            .value_at(&Point3::new(-2.0, -2.0, 0.0))
+            .0
+            .approx_eq(-0.25, MARGIN));
+        assert!(sdf
+            .value_at(&Point3::new(2.0, 2.0, 0.0))
+            .0
+            .approx_eq(-0.25, MARGIN));
+
+        let shifted = Mirror::new(
+            small_sphere_at(Point3::new(3.0, 0.0, 0.0)),
+            Vec3::new(1.0, 0.0, 0.0).unit(),
+            2.0,
+        );
+        assert!(shifted
+            .value_at(&Point3::new(1.0, 0.0, 0.0))
+            .0
+            .approx_eq(-0.25, MARGIN));
+        assert!(shifted.bounds().unwrap().min.0.x.approx_eq(0.75, MARGIN));
+    }
+
+    #[test]
+    fn cell_material_varies_per_cell() {
+        let mut materials = MaterialList::new();
+        let even = materials.insert(Material::default());
+        let odd = materials.insert(Material::default());
+        let sdf = WithCellMaterial::new(
+            Repeat::new(small_sphere(), Vec3::new(1.0, 1.0, 1.0)),
+            |c: CellIndex| {
+                Some(if (c.x + c.y + c.z) % 2 == 0 {
+                    even
+                } else {
+                    odd
+                })
+            },
+        );
+
+        let material_at = |p: Point3| sdf.value_at(&p).1.unwrap().dominant();
+        assert_eq!(material_at(Point3::new(0.0, 0.0, 0.0)), even);
+        assert_eq!(material_at(Point3::new(1.0, 0.0, 0.0)), odd);
+        assert_eq!(material_at(Point3::new(1.0, -1.0, 0.0)), even);
+    }
 }
@@ -1,6 +1,7 @@
-use crate::scene::scenemap::material::MaterialIndex;
+use crate::primitives::UnitVec3;
+use crate::scene::scenemap::material::MaterialBlend;
 use crate::scene::scenemap::sdf::Sdf;
-use crate::{Point3, Vec3};
+use crate::{Aabb, Point3, Vec3};
 
 #[derive(Debug, Clone)]
 pub struct Sphere {
@@ -21,12 +22,17 @@ impl Default for Sphere {
 }
 
 impl Sdf for Sphere {
-    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialIndex>) {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
         (
             (p - self.center.as_ref()).as_ref().length() - self.radius,
             None,
         )
     }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let r = self.radius;
+        Some(Aabb::from_center(&self.center, &Vec3::new(r, r, r)))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -55,7 +61,7 @@ impl Default for Cube {
 }
 
 impl Sdf for Cube {
-    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialIndex>) {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
         let d = (p - self.center.as_ref()).as_ref().abs()
             - Vec3::new(
                 self.half_side_length,
@@ -66,6 +72,740 @@ impl Sdf for Cube {
         let outside_distance = d.max(&Vec3::new(0.0, 0.0, 0.0)).length();
         (inside_distance + outside_distance, None)
     }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let h = self.half_side_length;
+        Some(Aabb::from_center(&self.center, &Vec3::new(h, h, h)))
+    }
+}
+
+/// Box with independent half extents along x, y and z. Exact.
+#[derive(Debug, Clone)]
+pub struct Cuboid {
+    half_extents: Vec3,
+    center: Point3,
+}
+
+impl Cuboid {
+    pub fn new(size: Vec3, center: Point3) -> Self {
+        Self::new_half_extents(size / 2.0, center)
+    }
+
+    pub fn new_half_extents(half_extents: Vec3, center: Point3) -> Self {
+        Self {
+            half_extents: half_extents.abs(),
+            center,
+        }
+    }
+}
+
+impl Sdf for Cuboid {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let q = &p.0 - &self.center.0;
+        (box_distance(&q, &self.half_extents), None)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb::from_center(&self.center, &self.half_extents))
+    }
+}
+
+/// Box whose edges and corners are rounded off with `radius`, keeping its outer half
+/// extents. Exact.
+#[derive(Debug, Clone)]
+pub struct RoundedBox {
+    half_extents: Vec3,
+    radius: f64,
+    center: Point3,
+}
+
+impl RoundedBox {
+    pub fn new(half_extents: Vec3, radius: f64, center: Point3) -> Self {
+        let half_extents = half_extents.abs();
+        let radius = radius.max(0.0).min(half_extents.min_component());
+        Self {
+            half_extents,
+            radius,
+            center,
+        }
+    }
+}
+
+impl Sdf for RoundedBox {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let q = &p.0 - &self.center.0;
+        let r = self.radius;
+        let inner = &self.half_extents - Vec3::new(r, r, r);
+        (box_distance(&q, &inner) - r, None)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb::from_center(&self.center, &self.half_extents))
+    }
+}
+
+/// Distance from `q` to a box around the origin.
+fn box_distance(q: &Vec3, half_extents: &Vec3) -> f64 {
+    let d = q.abs() - half_extents;
+    d.max_component().min(0.0) + d.max(&Vec3::ZERO).length()
+}
+
+/// Torus lying in the xz plane, with `major` the radius of the ring and `minor` the radius
+/// of the tube. Exact.
+#[derive(Debug, Clone)]
+pub struct Torus {
+    major: f64,
+    minor: f64,
+    center: Point3,
+}
+
+impl Torus {
+    pub fn new(major: f64, minor: f64, center: Point3) -> Self {
+        Self {
+            major,
+            minor,
+            center,
+        }
+    }
+}
+
+impl Sdf for Torus {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let q = &p.0 - &self.center.0;
+        ((q.x.hypot(q.z) - self.major).hypot(q.y) - self.minor, None)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let outer = self.major + self.minor;
+        Some(Aabb::from_center(
+            &self.center,
+            &Vec3::new(outer, self.minor, outer),
+        ))
+    }
+}
+
+/// Part of a torus lying in the xy plane, keeping the arc within `aperture` radians of the
+/// +y axis on either side. The open ends are rounded. Exact.
+#[derive(Debug, Clone)]
+pub struct CappedTorus {
+    sin: f64,
+    cos: f64,
+    major: f64,
+    minor: f64,
+    center: Point3,
+}
+
+impl CappedTorus {
+    pub fn new(aperture: f64, major: f64, minor: f64, center: Point3) -> Self {
+        let (sin, cos) = aperture.sin_cos();
+        Self {
+            sin,
+            cos,
+            major,
+            minor,
+            center,
+        }
+    }
+}
+
+impl Sdf for CappedTorus {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let q = &p.0 - &self.center.0;
+        let x = q.x.abs();
+        // Past the end of the arc the nearest point of the ring is its end point
+        let k = if self.cos * x > self.sin * q.y {
+            x * self.sin + q.y * self.cos
+        } else {
+            x.hypot(q.y)
+        };
+        let r = self.major;
+        (
+            (q.length_squared() + r * r - 2.0 * r * k).max(0.0).sqrt() - self.minor,
+            None,
+        )
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let outer = self.major + self.minor;
+        Some(Aabb::from_center(
+            &self.center,
+            &Vec3::new(outer, outer, self.minor),
+        ))
+    }
+}
+
+/// Chain link: a torus in the xy plane that is stretched by `half_length` on both sides
+/// along y. Exact.
+#[derive(Debug, Clone)]
+pub struct Link {
+    half_length: f64,
+    major: f64,
+    minor: f64,
+    center: Point3,
+}
+
+impl Link {
+    pub fn new(half_length: f64, major: f64, minor: f64, center: Point3) -> Self {
+        Self {
+            half_length,
+            major,
+            minor,
+            center,
+        }
+    }
+}
+
+impl Sdf for Link {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let q = &p.0 - &self.center.0;
+        let y = (q.y.abs() - self.half_length).max(0.0);
+        ((q.x.hypot(y) - self.major).hypot(q.z) - self.minor, None)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let outer = self.major + self.minor;
+        Some(Aabb::from_center(
+            &self.center,
+            &Vec3::new(outer, self.half_length + outer, self.minor),
+        ))
+    }
+}
+
+/// Cylinder of infinite length along the y axis through `center`. Exact.
+#[derive(Debug, Clone)]
+pub struct InfiniteCylinder {
+    radius: f64,
+    center: Point3,
+}
+
+impl InfiniteCylinder {
+    pub fn new(radius: f64, center: Point3) -> Self {
+        Self { radius, center }
+    }
+}
+
+impl Sdf for InfiniteCylinder {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let q = &p.0 - &self.center.0;
+        (q.x.hypot(q.z) - self.radius, None)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let (c, r) = (&self.center.0, self.radius);
+        Some(Aabb {
+            min: Point3::new(c.x - r, f64::NEG_INFINITY, c.z - r),
+            max: Point3::new(c.x + r, f64::INFINITY, c.z + r),
+        })
+    }
+}
+
+/// Cylinder along the y axis, closed by flat caps at `half_height` above and below
+/// `center`. Exact.
+#[derive(Debug, Clone)]
+pub struct Cylinder {
+    radius: f64,
+    half_height: f64,
+    center: Point3,
+}
+
+impl Cylinder {
+    pub fn new(radius: f64, half_height: f64, center: Point3) -> Self {
+        Self {
+            radius,
+            half_height,
+            center,
+        }
+    }
+}
+
+impl Sdf for Cylinder {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let q = &p.0 - &self.center.0;
+        let dx = q.x.hypot(q.z) - self.radius;
+        let dy = q.y.abs() - self.half_height;
+        (dx.max(dy).min(0.0) + dx.max(0.0).hypot(dy.max(0.0)), None)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let r = self.radius;
+        Some(Aabb::from_center(
+            &self.center,
+            &Vec3::new(r, self.half_height, r),
+        ))
+    }
+}
+
+/// Cone with its tip at `center`, opening downwards to a flat base of `radius` at `height`
+/// below the tip. Exact.
+#[derive(Debug, Clone)]
+pub struct Cone {
+    radius: f64,
+    height: f64,
+    center: Point3,
+}
+
+impl Cone {
+    pub fn new(radius: f64, height: f64, center: Point3) -> Self {
+        Self {
+            radius,
+            height,
+            center,
+        }
+    }
+}
+
+impl Sdf for Cone {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let q = &p.0 - &self.center.0;
+        // In the plane through the axis, the slanted side runs from the tip to (qx, qy)
+        let (qx, qy) = (self.radius, -self.height);
+        let (wx, wy) = (q.x.hypot(q.z), q.y);
+
+        let t = ((wx * qx + wy * qy) / (qx * qx + qy * qy)).clamp(0.0, 1.0);
+        let (ax, ay) = (wx - qx * t, wy - qy * t);
+        let u = (wx / qx).clamp(0.0, 1.0);
+        let (bx, by) = (wx - qx * u, wy - qy);
+
+        let d = (ax * ax + ay * ay).min(bx * bx + by * by);
+        let k = sign(qy);
+        let s = (k * (wx * qy - wy * qx)).max(k * (wy - qy));
+        (d.sqrt() * sign(s), None)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let (c, r) = (&self.center.0, self.radius);
+        Some(Aabb {
+            min: Point3::new(c.x - r, c.y - self.height, c.z - r),
+            max: Point3::new(c.x + r, c.y, c.z + r),
+        })
+    }
+}
+
+/// Cone cut off by flat caps at `half_height` above and below `center`, with radius
+/// `bottom_radius` at the bottom and `top_radius` at the top. Exact.
+#[derive(Debug, Clone)]
+pub struct CappedCone {
+    half_height: f64,
+    bottom_radius: f64,
+    top_radius: f64,
+    center: Point3,
+}
+
+impl CappedCone {
+    pub fn new(half_height: f64, bottom_radius: f64, top_radius: f64, center: Point3) -> Self {
+        Self {
+            half_height,
+            bottom_radius,
+            top_radius,
+            center,
+        }
+    }
+}
+
+impl Sdf for CappedCone {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let q = &p.0 - &self.center.0;
+        let (h, r1, r2) = (self.half_height, self.bottom_radius, self.top_radius);
+        let (qx, qy) = (q.x.hypot(q.z), q.y);
+
+        // Nearest point on the caps
+        let cap_radius = if qy < 0.0 { r1 } else { r2 };
+        let (cax, cay) = (qx - qx.min(cap_radius), qy.abs() - h);
+
+        // Nearest point on the slanted side, running from (r2, h) towards (r1, -h)
+        let (k2x, k2y) = (r2 - r1, 2.0 * h);
+        let t = (((r2 - qx) * k2x + (h - qy) * k2y) / (k2x * k2x + k2y * k2y)).clamp(0.0, 1.0);
+        let (cbx, cby) = (qx - r2 + k2x * t, qy - h + k2y * t);
+
+        let s = if cbx < 0.0 && cay < 0.0 { -1.0 } else { 1.0 };
+        (
+            s * (cax * cax + cay * cay).min(cbx * cbx + cby * cby).sqrt(),
+            None,
+        )
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let r = self.bottom_radius.max(self.top_radius);
+        Some(Aabb::from_center(
+            &self.center,
+            &Vec3::new(r, self.half_height, r),
+        ))
+    }
+}
+
+/// All points within `radius` of the line segment from `a` to `b`. With a radius of zero
+/// this is the segment itself. Exact.
+#[derive(Debug, Clone)]
+pub struct Capsule {
+    a: Point3,
+    b: Point3,
+    radius: f64,
+}
+
+impl Capsule {
+    pub fn new(a: Point3, b: Point3, radius: f64) -> Self {
+        Self { a, b, radius }
+    }
+}
+
+impl Sdf for Capsule {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let ba = &self.b.0 - &self.a.0;
+        let pa = &p.0 - &self.a.0;
+        (
+            segment_distance_squared(&ba, &pa).sqrt() - self.radius,
+            None,
+        )
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Aabb::from_points(vec![self.a.clone(), self.b.clone()]).map(|b| b.expand(self.radius))
+    }
+}
+
+/// Hexagonal prism along the z axis. `apothem` is the distance from the axis to the flat
+/// sides, which face up and down. Exact.
+#[derive(Debug, Clone)]
+pub struct HexPrism {
+    apothem: f64,
+    half_length: f64,
+    center: Point3,
+}
+
+impl HexPrism {
+    pub fn new(apothem: f64, half_length: f64, center: Point3) -> Self {
+        Self {
+            apothem,
+            half_length,
+            center,
+        }
+    }
+}
+
+impl Sdf for HexPrism {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        // (-cos 30°, sin 30°) and tan 30°
+        const K: (f64, f64, f64) = (-0.866_025_403_784_438_6, 0.5, 0.577_350_269_189_625_8);
+        let q = (&p.0 - &self.center.0).abs();
+        let h = self.apothem;
+
+        // Fold the slanted sides onto the top one
+        let fold = 2.0 * (K.0 * q.x + K.1 * q.y).min(0.0);
+        let (x, y) = (q.x - fold * K.0, q.y - fold * K.1);
+
+        let edge_x = x.clamp(-K.2 * h, K.2 * h);
+        let dx = (x - edge_x).hypot(y - h) * sign(y - h);
+        let dz = q.z - self.half_length;
+        (dx.max(dz).min(0.0) + dx.max(0.0).hypot(dz.max(0.0)), None)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let circumradius = self.apothem * 2.0 / 3f64.sqrt();
+        Some(Aabb::from_center(
+            &self.center,
+            &Vec3::new(circumradius, self.apothem, self.half_length),
+        ))
+    }
+}
+
+/// Prism along the z axis with an equilateral triangle as cross section, pointing up.
+/// `radius` is the distance from the axis to the corners. A bound, not exact near the
+/// corners.
+#[derive(Debug, Clone)]
+pub struct TriPrism {
+    radius: f64,
+    half_length: f64,
+    center: Point3,
+}
+
+impl TriPrism {
+    pub fn new(radius: f64, half_length: f64, center: Point3) -> Self {
+        Self {
+            radius,
+            half_length,
+            center,
+        }
+    }
+}
+
+impl Sdf for TriPrism {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let q = &p.0 - &self.center.0;
+        let cos30 = 3f64.sqrt() / 2.0;
+        let triangle = (q.x.abs() * cos30 + q.y * 0.5).max(-q.y) - self.radius * 0.5;
+        (triangle.max(q.z.abs() - self.half_length), None)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let (c, r, l) = (&self.center.0, self.radius, self.half_length);
+        let half_width = r * 3f64.sqrt() / 2.0;
+        Some(Aabb {
+            min: Point3::new(c.x - half_width, c.y - r / 2.0, c.z - l),
+            max: Point3::new(c.x + half_width, c.y + r, c.z + l),
+        })
+    }
+}
+
+/// Regular octahedron with its corners at `size` from `center` along each axis. Exact.
+#[derive(Debug, Clone)]
+pub struct Octahedron {
+    size: f64,
+    center: Point3,
+}
+
+impl Octahedron {
+    pub fn new(size: f64, center: Point3) -> Self {
+        Self { size, center }
+    }
+}
+
+impl Sdf for Octahedron {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let q = (&p.0 - &self.center.0).abs();
+        let s = self.size;
+        let m = q.x + q.y + q.z - s;
+        // Rotate the axes so the one whose edge region contains q comes first
+        let (x, y, z) = if 3.0 * q.x < m {
+            (q.x, q.y, q.z)
+        } else if 3.0 * q.y < m {
+            (q.y, q.z, q.x)
+        } else if 3.0 * q.z < m {
+            (q.z, q.x, q.y)
+        } else {
+            return (m / 3f64.sqrt(), None);
+        };
+        let k = (0.5 * (z - y + s)).clamp(0.0, s);
+        (Vec3::new(x, y - s + k, z - k).length(), None)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let s = self.size;
+        Some(Aabb::from_center(&self.center, &Vec3::new(s, s, s)))
+    }
+}
+
+/// Pyramid with a square base of `base` by `base` centered on `center` and its apex
+/// `height` above it. Exact.
+#[derive(Debug, Clone)]
+pub struct Pyramid {
+    base: f64,
+    height: f64,
+    center: Point3,
+}
+
+impl Pyramid {
+    pub fn new(base: f64, height: f64, center: Point3) -> Self {
+        Self {
+            base,
+            height,
+            center,
+        }
+    }
+}
+
+impl Sdf for Pyramid {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        // Work on a pyramid with a unit base and scale the distance back afterwards
+        let w = self.base;
+        let q = (&p.0 - &self.center.0) / w;
+        let h = self.height / w;
+        let m2 = h * h + 0.25;
+
+        let (x, z) = (q.x.abs(), q.z.abs());
+        if q.y < 0.0 {
+            // The pyramid only narrows upwards, so below it the base is always nearest
+            let base = Vec3::new((x - 0.5).max(0.0), q.y, (z - 0.5).max(0.0));
+            return (w * base.length(), None);
+        }
+        let (x, z) = if z > x { (z, x) } else { (x, z) };
+        let (x, z) = (x - 0.5, z - 0.5);
+
+        let (qx, qy, qz) = (z, h * q.y - 0.5 * x, h * x + 0.5 * q.y);
+        let s = (-qx).max(0.0);
+        let t = ((qy - 0.5 * z) / (m2 + 0.25)).clamp(0.0, 1.0);
+        let a = m2 * (qx + s).powi(2) + qy * qy;
+        let b = m2 * (qx + 0.5 * t).powi(2) + (qy - m2 * t).powi(2);
+        let d2 = if qy.min(-qx * m2 - qy * 0.5) > 0.0 {
+            0.0
+        } else {
+            a.min(b)
+        };
+        let d = ((d2 + qz * qz) / m2).sqrt() * sign(qz.max(-q.y));
+        // Inside, the base is as much a face as the slanted sides
+        (w * d.max(-q.y), None)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let (c, r) = (&self.center.0, self.base / 2.0);
+        Some(Aabb {
+            min: Point3::new(c.x - r, c.y, c.z - r),
+            max: Point3::new(c.x + r, c.y + self.height, c.z + r),
+        })
+    }
+}
+
+/// Ellipsoid with semi-axes `radii` along x, y and z.
+///
+/// Unlike a scaled sphere this gives exact distances, which are found by bisection as
+/// described in David Eberly's "Distance from a Point to an Ellipse, an Ellipsoid, or a
+/// Hyperellipsoid". That makes it slower than the other primitives.
+#[derive(Debug, Clone)]
+pub struct Ellipsoid {
+    radii: Vec3,
+    center: Point3,
+}
+
+impl Ellipsoid {
+    /// Smallest semi-axis, to keep flat ellipsoids from dividing by zero.
+    const MIN_RADIUS: f64 = 1e-6;
+
+    /// Radii below `1e-6` are clamped to it.
+    pub fn new(radii: Vec3, center: Point3) -> Self {
+        let min = Vec3::new(Self::MIN_RADIUS, Self::MIN_RADIUS, Self::MIN_RADIUS);
+        Self {
+            radii: radii.abs().max(&min),
+            center,
+        }
+    }
+}
+
+impl Sdf for Ellipsoid {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        // By symmetry only the first octant is needed, with the radii in decreasing order.
+        let local = (p - self.center.as_ref()).0.abs();
+        let mut axes = [
+            (self.radii.x, local.x),
+            (self.radii.y, local.y),
+            (self.radii.z, local.z),
+        ];
+        axes.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        let [(e0, y0), (e1, y1), (e2, y2)] = axes;
+
+        let d = distance_to_ellipsoid(e0, e1, e2, y0, y1, y2);
+        let inside = (y0 / e0).powi(2) + (y1 / e1).powi(2) + (y2 / e2).powi(2) < 1.0;
+        (if inside { -d } else { d }, None)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb::from_center(&self.center, &self.radii))
+    }
+}
+
+/// Upper bound on the bisection steps, which normally stop much earlier once the interval
+/// is within [BISECTION_EPSILON].
+const MAX_BISECTIONS: usize = 256;
+
+/// Width, relative to the root once that is larger than one, at which bisection stops.
+const BISECTION_EPSILON: f64 = 1e-12;
+
+/// Finds the root of the decreasing function `g` in `[s0, s1]`.
+fn bisect(mut s0: f64, mut s1: f64, g: impl Fn(f64) -> f64) -> f64 {
+    let mut s = s0;
+    for _ in 0..MAX_BISECTIONS {
+        s = 0.5 * (s0 + s1);
+        if s1 - s0 <= BISECTION_EPSILON * s.abs().max(1.0) {
+            break;
+        }
+        let v = g(s);
+        if v > 0.0 {
+            s0 = s;
+        } else if v < 0.0 {
+            s1 = s;
+        } else {
+            break;
+        }
+    }
+    s
+}
+
+/// Distance from `(y0, y1)` to the ellipse with semi-axes `e0 >= e1`, with `y0, y1 >= 0`.
+fn distance_to_ellipse(e0: f64, e1: f64, y0: f64, y1: f64) -> f64 {
+    if y1 > 0.0 {
+        if y0 > 0.0 {
+            let (z0, z1) = (y0 / e0, y1 / e1);
+            let g = z0 * z0 + z1 * z1 - 1.0;
+            if g == 0.0 {
+                return 0.0;
+            }
+            let r0 = (e0 / e1).powi(2);
+            let n0 = r0 * z0;
+            let s1 = if g < 0.0 { 0.0 } else { n0.hypot(z1) - 1.0 };
+            let s = bisect(z1 - 1.0, s1, |s| {
+                (n0 / (s + r0)).powi(2) + (z1 / (s + 1.0)).powi(2) - 1.0
+            });
+            let x0 = r0 * y0 / (s + r0);
+            let x1 = y1 / (s + 1.0);
+            (x0 - y0).hypot(x1 - y1)
+        } else {
+            (y1 - e1).abs()
+        }
+    } else {
+        let numer0 = e0 * y0;
+        let denom0 = e0 * e0 - e1 * e1;
+        if numer0 < denom0 {
+            let xde0 = numer0 / denom0;
+            let x0 = e0 * xde0;
+            let x1 = e1 * (1.0 - xde0 * xde0).sqrt();
+            (x0 - y0).hypot(x1)
+        } else {
+            (y0 - e0).abs()
+        }
+    }
+}
+
+/// Distance from `(y0, y1, y2)` to the ellipsoid with semi-axes `e0 >= e1 >= e2`, with
+/// `y0, y1, y2 >= 0`.
+fn distance_to_ellipsoid(e0: f64, e1: f64, e2: f64, y0: f64, y1: f64, y2: f64) -> f64 {
+    if y2 > 0.0 {
+        if y1 > 0.0 {
+            if y0 > 0.0 {
+                let (z0, z1, z2) = (y0 / e0, y1 / e1, y2 / e2);
+                let g = z0 * z0 + z1 * z1 + z2 * z2 - 1.0;
+                if g == 0.0 {
+                    return 0.0;
+                }
+                let r0 = (e0 / e2).powi(2);
+                let r1 = (e1 / e2).powi(2);
+                let (n0, n1) = (r0 * z0, r1 * z1);
+                let s1 = if g < 0.0 {
+                    0.0
+                } else {
+                    n0.hypot(n1).hypot(z2) - 1.0
+                };
+                let s = bisect(z2 - 1.0, s1, |s| {
+                    (n0 / (s + r0)).powi(2) + (n1 / (s + r1)).powi(2) + (z2 / (s + 1.0)).powi(2)
+                        - 1.0
+                });
+                let x0 = r0 * y0 / (s + r0);
+                let x1 = r1 * y1 / (s + r1);
+                let x2 = y2 / (s + 1.0);
+                Vec3::new(x0 - y0, x1 - y1, x2 - y2).length()
+            } else {
+                distance_to_ellipse(e1, e2, y1, y2)
+            }
+        } else if y0 > 0.0 {
+            distance_to_ellipse(e0, e2, y0, y2)
+        } else {
+            (y2 - e2).abs()
+        }
+    } else {
+        let denom0 = e0 * e0 - e2 * e2;
+        let denom1 = e1 * e1 - e2 * e2;
+        let numer0 = e0 * y0;
+        let numer1 = e1 * y1;
+        if numer0 < denom0 && numer1 < denom1 {
+            let xde0 = numer0 / denom0;
+            let xde1 = numer1 / denom1;
+            let discr = 1.0 - xde0 * xde0 - xde1 * xde1;
+            if discr > 0.0 {
+                let x0 = e0 * xde0;
+                let x1 = e1 * xde1;
+                let x2 = e2 * discr.sqrt();
+                return Vec3::new(x0 - y0, x1 - y1, x2).length();
+            }
+        }
+        distance_to_ellipse(e0, e1, y0, y1)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -76,17 +816,17 @@ pub struct Arbitrary<S> {
 impl<S> Arbitrary<S> {
     pub fn new<'a>(s: S) -> Self
     where
-        S: (Fn(&Point3) -> (f64, Option<MaterialIndex>)) + 'a,
+        S: (Fn(&Point3) -> (f64, Option<MaterialBlend>)) + Send + Sync + 'a,
     {
         Self { s }
     }
 }
 
-impl<'a, S> Sdf for Arbitrary<S>
+impl<S> Sdf for Arbitrary<S>
 where
-    S: (Fn(&Point3) -> (f64, Option<MaterialIndex>)) + 'a,
+    S: (Fn(&Point3) -> (f64, Option<MaterialBlend>)) + Send + Sync,
 {
-    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialIndex>) {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
         (self.s)(p)
     }
 }
@@ -94,7 +834,620 @@ where
 pub struct NegY;
 
 impl Sdf for NegY {
-    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialIndex>) {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
         (p.0.y, None)
     }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb {
+            max: Point3::new(f64::INFINITY, 0.0, f64::INFINITY),
+            ..Aabb::INFINITE
+        })
+    }
+}
+
+/// Half space of the points `p` with `p . normal < offset`. Exact.
+#[derive(Debug, Clone)]
+pub struct Plane {
+    normal: UnitVec3,
+    offset: f64,
+}
+
+impl Plane {
+    pub fn new(normal: UnitVec3, offset: f64) -> Self {
+        Self { normal, offset }
+    }
+}
+
+impl Sdf for Plane {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        (p.0.dot(&self.normal.0) - self.offset, None)
+    }
+
+    /// Only bounded on one side, and only when the normal is along one of the axes.
+    fn bounds(&self) -> Option<Aabb> {
+        let n = &self.normal.0;
+        let mut bounds = Aabb::INFINITE;
+        let (lower, upper, component) = if n.y == 0.0 && n.z == 0.0 {
+            (&mut bounds.min.0.x, &mut bounds.max.0.x, n.x)
+        } else if n.x == 0.0 && n.z == 0.0 {
+            (&mut bounds.min.0.y, &mut bounds.max.0.y, n.y)
+        } else if n.x == 0.0 && n.y == 0.0 {
+            (&mut bounds.min.0.z, &mut bounds.max.0.z, n.z)
+        } else {
+            return None;
+        };
+        if component > 0.0 {
+            *upper = self.offset / component;
+        } else {
+            *lower = self.offset / component;
+        }
+        Some(bounds)
+    }
+}
+
+/// Triangle with corners `a`, `b` and `c`. It has no thickness, so the distance is
+/// unsigned. Exact.
+#[derive(Debug, Clone)]
+pub struct Triangle {
+    a: Point3,
+    b: Point3,
+    c: Point3,
+}
+
+impl Triangle {
+    pub fn new(a: Point3, b: Point3, c: Point3) -> Self {
+        Self { a, b, c }
+    }
+}
+
+impl Sdf for Triangle {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let (a, b, c, p) = (&self.a.0, &self.b.0, &self.c.0, &p.0);
+        let edges = [(b - a, p - a), (c - b, p - b), (a - c, p - c)];
+        let normal = edges[0].0.cross(&edges[2].0);
+        (polygon_distance(&edges, &normal), None)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Aabb::from_points(vec![self.a.clone(), self.b.clone(), self.c.clone()])
+    }
+}
+
+/// Planar quad with corners `a`, `b`, `c` and `d` in order. It has no thickness, so the
+/// distance is unsigned. Exact.
+#[derive(Debug, Clone)]
+pub struct Quad {
+    a: Point3,
+    b: Point3,
+    c: Point3,
+    d: Point3,
+}
+
+impl Quad {
+    pub fn new(a: Point3, b: Point3, c: Point3, d: Point3) -> Self {
+        Self { a, b, c, d }
+    }
+}
+
+impl Sdf for Quad {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let (a, b, c, d, p) = (&self.a.0, &self.b.0, &self.c.0, &self.d.0, &p.0);
+        let edges = [
+            (b - a, p - a),
+            (c - b, p - b),
+            (d - c, p - c),
+            (a - d, p - d),
+        ];
+        let normal = edges[0].0.cross(&edges[3].0);
+        (polygon_distance(&edges, &normal), None)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Aabb::from_points(vec![
+            self.a.clone(),
+            self.b.clone(),
+            self.c.clone(),
+            self.d.clone(),
+        ])
+    }
+}
+
+/// Unsigned distance to a flat convex polygon, given as `(edge, p - edge start)` pairs.
+fn polygon_distance(edges: &[(Vec3, Vec3)], normal: &Vec3) -> f64 {
+    // Above the polygon when p is on the inner side of every edge
+    let inside: f64 = edges
+        .iter()
+        .map(|(edge, offset)| sign(edge.cross(normal).dot(offset)))
+        .sum();
+    let d2 = if inside < (edges.len() - 1) as f64 {
+        edges
+            .iter()
+            .map(|(edge, offset)| segment_distance_squared(edge, offset))
+            .fold(f64::INFINITY, f64::min)
+    } else {
+        normal.dot(&edges[0].1).powi(2) / normal.length_squared()
+    };
+    d2.sqrt()
+}
+
+/// Squared distance from the segment starting at the origin along `edge` to `offset`.
+pub(super) fn segment_distance_squared(edge: &Vec3, offset: &Vec3) -> f64 {
+    let length_squared = edge.length_squared();
+    let t = if length_squared > 0.0 {
+        (edge.dot(offset) / length_squared).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (edge * t - offset).length_squared()
+}
+
+/// Sign of `v`, zero for zero.
+fn sign(v: f64) -> f64 {
+    if v > 0.0 {
+        1.0
+    } else if v < 0.0 {
+        -1.0
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_PI_2;
+
+    use float_cmp::ApproxEq;
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::raymarcher::{FindTargetSettings, Ray};
+    use crate::scene::scenemap::sdf::positioners::Scale;
+    use crate::test_constants::MARGIN;
+
+    /// Distance along the ray from `origin` in `direction` to the surface of `sdf`.
+    fn hit(sdf: &dyn Sdf, origin: Point3, direction: Vec3) -> Option<f64> {
+        let settings = FindTargetSettings::new(0.0, 100.0, 1e-9);
+        Ray::new_unnormalized(origin, direction)
+            .find_target(&settings, sdf)
+            .map(|result| result.distance)
+    }
+
+    fn assert_hits(sdf: &dyn Sdf, origin: Point3, direction: Vec3, expected: f64) {
+        let distance = hit(sdf, origin, direction).expect("ray should hit");
+        assert!(
+            (distance - expected).abs() < 1e-6,
+            "expected {}, got {}",
+            expected,
+            distance
+        );
+    }
+
+    fn extended_primitives() -> Vec<Box<dyn Sdf>> {
+        vec![
+            Box::new(Cuboid::new(Vec3::new(2.0, 4.0, 6.0), Point3::ORIGIN)),
+            Box::new(RoundedBox::new(
+                Vec3::new(1.0, 1.0, 1.0),
+                0.25,
+                Point3::ORIGIN,
+            )),
+            Box::new(Torus::new(1.0, 0.25, Point3::ORIGIN)),
+            Box::new(CappedTorus::new(FRAC_PI_2, 1.0, 0.25, Point3::ORIGIN)),
+            Box::new(Link::new(0.5, 1.0, 0.25, Point3::ORIGIN)),
+            Box::new(Cylinder::new(1.0, 2.0, Point3::ORIGIN)),
+            Box::new(Cone::new(1.0, 2.0, Point3::ORIGIN)),
+            Box::new(CappedCone::new(1.0, 1.0, 0.5, Point3::ORIGIN)),
+            Box::new(Capsule::new(
+                Point3::new(0.0, -1.0, 0.0),
+                Point3::new(0.0, 1.0, 0.0),
+                0.5,
+            )),
+            Box::new(HexPrism::new(1.0, 2.0, Point3::ORIGIN)),
+            Box::new(TriPrism::new(1.0, 2.0, Point3::ORIGIN)),
+            Box::new(Octahedron::new(1.0, Point3::ORIGIN)),
+            Box::new(Pyramid::new(2.0, 1.0, Point3::ORIGIN)),
+            Box::new(Triangle::new(
+                Point3::new(-1.0, 0.0, -1.0),
+                Point3::new(1.0, 0.0, -1.0),
+                Point3::new(0.0, 0.0, 1.0),
+            )),
+            Box::new(Quad::new(
+                Point3::new(-1.0, -1.0, 0.0),
+                Point3::new(1.0, -1.0, 0.0),
+                Point3::new(1.0, 1.0, 0.0),
+                Point3::new(-1.0, 1.0, 0.0),
+            )),
+        ]
+    }
+
+    #[test]
+    fn boxes_hit_analytically() {
+        let cuboid = Cuboid::new(Vec3::new(2.0, 4.0, 6.0), Point3::ORIGIN);
+        assert_hits(
+            &cuboid,
+            Point3::new(0.0, 0.0, 10.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            7.0,
+        );
+        assert_hits(
+            &cuboid,
+            Point3::new(0.0, 10.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            8.0,
+        );
+
+        let rounded = RoundedBox::new(Vec3::new(1.0, 1.0, 1.0), 0.25, Point3::ORIGIN);
+        assert_hits(
+            &rounded,
+            Point3::new(10.0, 0.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+            9.0,
+        );
+        // Along the diagonal the corner is rounded off
+        let corner = 3f64.sqrt() * (5.0 - 0.75) - 0.25;
+        assert_hits(
+            &rounded,
+            Point3::new(5.0, 5.0, 5.0),
+            Vec3::new(-1.0, -1.0, -1.0),
+            corner,
+        );
+    }
+
+    #[test]
+    fn tori_hit_analytically() {
+        let torus = Torus::new(1.0, 0.25, Point3::ORIGIN);
+        assert_hits(
+            &torus,
+            Point3::new(1.0, 10.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            9.75,
+        );
+        assert_hits(
+            &torus,
+            Point3::new(10.0, 0.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+            8.75,
+        );
+        assert!(hit(
+            &torus,
+            Point3::new(0.0, 10.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0)
+        )
+        .is_none());
+
+        // Only the upper half of the ring is kept
+        let capped = CappedTorus::new(FRAC_PI_2, 1.0, 0.25, Point3::ORIGIN);
+        assert_hits(
+            &capped,
+            Point3::new(0.0, 10.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            8.75,
+        );
+        assert_hits(
+            &capped,
+            Point3::new(0.0, -10.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            10.75,
+        );
+
+        let link = Link::new(0.5, 1.0, 0.25, Point3::ORIGIN);
+        assert_hits(
+            &link,
+            Point3::new(0.0, 10.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            8.25,
+        );
+        assert_hits(
+            &link,
+            Point3::new(10.0, 0.5, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+            8.75,
+        );
+    }
+
+    #[test]
+    fn cylinders_and_cones_hit_analytically() {
+        let infinite = InfiniteCylinder::new(1.0, Point3::ORIGIN);
+        assert_hits(
+            &infinite,
+            Point3::new(10.0, 5.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+            9.0,
+        );
+
+        let cylinder = Cylinder::new(1.0, 2.0, Point3::ORIGIN);
+        assert_hits(
+            &cylinder,
+            Point3::new(0.0, 10.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            8.0,
+        );
+        assert_hits(
+            &cylinder,
+            Point3::new(10.0, 1.5, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+            9.0,
+        );
+
+        let cone = Cone::new(1.0, 2.0, Point3::ORIGIN);
+        assert_hits(
+            &cone,
+            Point3::new(0.0, -10.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            8.0,
+        );
+        assert_hits(
+            &cone,
+            Point3::new(10.0, -1.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+            9.5,
+        );
+
+        let capped = CappedCone::new(1.0, 1.0, 0.5, Point3::ORIGIN);
+        assert_hits(
+            &capped,
+            Point3::new(10.0, 0.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+            9.25,
+        );
+        assert_hits(
+            &capped,
+            Point3::new(0.0, 10.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            9.0,
+        );
+
+        let capsule = Capsule::new(Point3::new(0.0, -1.0, 0.0), Point3::new(0.0, 1.0, 0.0), 0.5);
+        assert_hits(
+            &capsule,
+            Point3::new(0.0, 10.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            8.5,
+        );
+        assert_hits(
+            &capsule,
+            Point3::new(10.0, 0.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+            9.5,
+        );
+    }
+
+    #[test]
+    fn polyhedra_hit_analytically() {
+        let hex = HexPrism::new(1.0, 2.0, Point3::ORIGIN);
+        assert_hits(
+            &hex,
+            Point3::new(0.0, 10.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            9.0,
+        );
+        assert_hits(
+            &hex,
+            Point3::new(10.0, 0.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+            10.0 - 2.0 / 3f64.sqrt(),
+        );
+        assert_hits(
+            &hex,
+            Point3::new(0.0, 0.0, 10.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            8.0,
+        );
+
+        let tri = TriPrism::new(1.0, 2.0, Point3::ORIGIN);
+        assert_hits(
+            &tri,
+            Point3::new(0.0, -10.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            9.5,
+        );
+        assert_hits(
+            &tri,
+            Point3::new(0.0, 0.0, 10.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            8.0,
+        );
+
+        let octahedron = Octahedron::new(1.0, Point3::ORIGIN);
+        assert_hits(
+            &octahedron,
+            Point3::new(10.0, 0.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+            9.0,
+        );
+        let face = 3f64.sqrt() * (5.0 - 1.0 / 3.0);
+        assert_hits(
+            &octahedron,
+            Point3::new(5.0, 5.0, 5.0),
+            Vec3::new(-1.0, -1.0, -1.0),
+            face,
+        );
+
+        let pyramid = Pyramid::new(2.0, 1.0, Point3::ORIGIN);
+        assert_hits(
+            &pyramid,
+            Point3::new(0.0, 10.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            9.0,
+        );
+        assert_hits(
+            &pyramid,
+            Point3::new(0.0, -10.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            10.0,
+        );
+        assert_hits(
+            &pyramid,
+            Point3::new(10.0, 0.5, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+            9.5,
+        );
+
+        let ellipsoid = ellipsoid();
+        assert_hits(
+            &ellipsoid,
+            Point3::new(0.5, 10.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            7.0,
+        );
+        assert_hits(
+            &ellipsoid,
+            Point3::new(10.0, 0.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+            8.5,
+        );
+    }
+
+    #[test]
+    fn planes_and_polygons_hit_analytically() {
+        let plane = Plane::new(Vec3::new(0.0, 1.0, 0.0).unit(), 1.0);
+        assert_hits(
+            &plane,
+            Point3::new(0.0, 10.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            9.0,
+        );
+        let tilted = Plane::new(Vec3::new(1.0, 1.0, 0.0).unit(), 0.0);
+        assert_hits(
+            &tilted,
+            Point3::new(5.0, 5.0, 0.0),
+            Vec3::new(-1.0, -1.0, 0.0),
+            50f64.sqrt(),
+        );
+        assert!(tilted.bounds().is_none());
+
+        let triangle = Triangle::new(
+            Point3::new(-1.0, 0.0, -1.0),
+            Point3::new(1.0, 0.0, -1.0),
+            Point3::new(0.0, 0.0, 1.0),
+        );
+        assert_hits(
+            &triangle,
+            Point3::new(0.0, 10.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            10.0,
+        );
+        assert!(hit(
+            &triangle,
+            Point3::new(5.0, 10.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0)
+        )
+        .is_none());
+        // Distance to an edge from outside the triangle
+        assert!(triangle
+            .value_at(&Point3::new(0.0, 3.0, -5.0))
+            .0
+            .approx_eq(5.0, MARGIN));
+
+        let quad = Quad::new(
+            Point3::new(-1.0, -1.0, 0.0),
+            Point3::new(1.0, -1.0, 0.0),
+            Point3::new(1.0, 1.0, 0.0),
+            Point3::new(-1.0, 1.0, 0.0),
+        );
+        assert_hits(
+            &quad,
+            Point3::new(0.5, 0.5, 10.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            10.0,
+        );
+        assert!(quad
+            .value_at(&Point3::new(4.0, 5.0, 0.0))
+            .0
+            .approx_eq(5.0, MARGIN));
+    }
+
+    #[test]
+    fn extended_primitives_are_inside_bounds() {
+        for sdf in extended_primitives().iter() {
+            let bounds = sdf.bounds().unwrap();
+            for corner in bounds.expand(1e-3).corners().iter() {
+                assert!(sdf.value_at(corner).0 > 0.0);
+            }
+        }
+    }
+
+    fn ellipsoid() -> Ellipsoid {
+        Ellipsoid::new(Vec3::new(1.0, 3.0, 2.0), Point3::new(0.5, 0.0, 0.0))
+    }
+
+    #[test]
+    fn ellipsoid_is_exact_along_axes() {
+        let sdf = ellipsoid();
+        let d = |x, y, z| sdf.value_at(&Point3::new(x, y, z)).0;
+
+        assert!(d(2.5, 0.0, 0.0).approx_eq(1.0, MARGIN));
+        assert!(d(0.5, -5.0, 0.0).approx_eq(2.0, MARGIN));
+        assert!(d(0.5, 0.0, 2.5).approx_eq(0.5, MARGIN));
+        // The closest point from the center is at the end of the shortest axis
+        assert!(d(0.5, 0.0, 0.0).approx_eq(-1.0, MARGIN));
+    }
+
+    #[test]
+    fn ellipsoid_with_equal_radii_is_sphere() {
+        let ellipsoid = Ellipsoid::new(Vec3::new(2.0, 2.0, 2.0), Point3::ORIGIN);
+        let sphere = Sphere::new(2.0, Point3::ORIGIN);
+        for p in [
+            Point3::new(1.0, 2.0, 3.0),
+            Point3::new(0.3, -0.2, 0.1),
+            Point3::new(0.0, 0.0, 5.0),
+        ]
+        .iter()
+        {
+            assert!(ellipsoid
+                .value_at(p)
+                .0
+                .approx_eq(sphere.value_at(p).0, MARGIN));
+        }
+    }
+
+    #[test]
+    fn flat_ellipsoid_has_finite_distances() {
+        let disk = Ellipsoid::new(Vec3::new(1.0, 0.0, 1.0), Point3::ORIGIN);
+        let d = |x, y, z| disk.value_at(&Point3::new(x, y, z)).0;
+
+        assert!(d(0.0, 2.0, 0.0).approx_eq(2.0, MARGIN));
+        assert!(d(0.3, 0.5, 0.2).approx_eq(0.5, MARGIN));
+        assert!(d(3.0, 0.0, 0.0).approx_eq(2.0, MARGIN));
+        assert!(d(0.0, 0.0, 0.0).is_finite());
+    }
+
+    proptest! {
+        #[test]
+        fn ellipsoid_distance_is_lipschitz(
+            x in -5.0..5.0, y in -5.0..5.0, z in -5.0..5.0,
+            dx in -0.1..0.1, dy in -0.1..0.1, dz in -0.1..0.1,
+        ) {
+            let sdf = ellipsoid();
+            let p = Point3::new(x, y, z);
+            let step = Vec3::new(dx, dy, dz);
+            let q = &p + &step;
+            let change = (sdf.value_at(&p).0 - sdf.value_at(&q).0).abs();
+            prop_assert!(change <= step.length() + 1e-9);
+        }
+
+        #[test]
+        fn scaled_sphere_underestimates_ellipsoid(x in -5.0..5.0, y in -5.0..5.0, z in -5.0..5.0) {
+            let exact = Ellipsoid::new(Vec3::new(1.0, 3.0, 2.0), Point3::ORIGIN);
+            let scaled = Scale::new(Sphere::default(), Vec3::new(1.0, 3.0, 2.0));
+            let p = Point3::new(x, y, z);
+            let (e, s) = (exact.value_at(&p).0, scaled.value_at(&p).0);
+            prop_assert!(e.signum() == s.signum());
+            prop_assert!(s.abs() <= e.abs() + 1e-9);
+        }
+
+        #[test]
+        fn extended_primitives_are_lipschitz(
+            x in -3.0..3.0, y in -3.0..3.0, z in -3.0..3.0,
+            dx in -0.1..0.1, dy in -0.1..0.1, dz in -0.1..0.1,
+        ) {
+            let p = Point3::new(x, y, z);
+            let step = Vec3::new(dx, dy, dz);
+            let q = &p + &step;
+            for sdf in extended_primitives().iter() {
+                let change = (sdf.value_at(&p).0 - sdf.value_at(&q).0).abs();
+                prop_assert!(change <= step.length() + 1e-9);
+            }
+        }
+    }
 }
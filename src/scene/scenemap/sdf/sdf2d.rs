@@ -0,0 +1,545 @@
+//! Signed distance functions in the plane, and adapters that lift them into 3D.
+//!
+//! A 2D SDF describes a profile that can be reused across 3D shapes: [Revolve] turns it
+//! around an axis like a lathe, [Extrude] pushes it out along one. Bounds of 2D SDFs are
+//! [Aabb]s in the xy plane with a zero z extent.
+
+use std::f64::consts::PI;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use crate::scene::scenemap::material::MaterialBlend;
+use crate::scene::scenemap::sdf::positioners::Axis;
+use crate::scene::scenemap::sdf::Sdf;
+use crate::{Aabb, Point3, Vec2};
+
+/// Signed distance function in the plane.
+pub trait Sdf2d: Send + Sync {
+    fn distance_at(&self, p: Vec2) -> f64;
+
+    /// Rectangle containing every point where the SDF is negative, or [None] if it is
+    /// unbounded. Follows the same rules as [Sdf::bounds].
+    fn bounds(&self) -> Option<Aabb> {
+        None
+    }
+}
+
+impl<A: Sdf2d + ?Sized> Sdf2d for &A {
+    fn distance_at(&self, p: Vec2) -> f64 {
+        (*self).distance_at(p)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        (*self).bounds()
+    }
+}
+
+impl<A: Sdf2d + ?Sized> Sdf2d for Box<A> {
+    fn distance_at(&self, p: Vec2) -> f64 {
+        self.deref().distance_at(p)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.deref().bounds()
+    }
+}
+
+impl<A: Sdf2d + ?Sized> Sdf2d for Arc<A> {
+    fn distance_at(&self, p: Vec2) -> f64 {
+        self.deref().distance_at(p)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.deref().bounds()
+    }
+}
+
+/// Rectangle in the xy plane from `min` to `max`.
+fn rect(min: Vec2, max: Vec2) -> Aabb {
+    Aabb::new(
+        Point3::new(min.x, min.y, 0.0),
+        Point3::new(max.x, max.y, 0.0),
+    )
+}
+
+#[derive(Debug, Clone)]
+pub struct Circle {
+    radius: f64,
+    center: Vec2,
+}
+
+impl Circle {
+    pub fn new(radius: f64, center: Vec2) -> Self {
+        Self { radius, center }
+    }
+}
+
+impl Sdf2d for Circle {
+    fn distance_at(&self, p: Vec2) -> f64 {
+        (p - self.center).length() - self.radius
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let r = Vec2::new(self.radius, self.radius);
+        Some(rect(self.center - r, self.center + r))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Rectangle {
+    half_extents: Vec2,
+    center: Vec2,
+}
+
+impl Rectangle {
+    pub fn new(size: Vec2, center: Vec2) -> Self {
+        Self::new_half_extents(size / 2.0, center)
+    }
+
+    pub fn new_half_extents(half_extents: Vec2, center: Vec2) -> Self {
+        Self {
+            half_extents: half_extents.abs(),
+            center,
+        }
+    }
+}
+
+impl Sdf2d for Rectangle {
+    fn distance_at(&self, p: Vec2) -> f64 {
+        rectangle_distance(p - self.center, self.half_extents)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(rect(
+            self.center - self.half_extents,
+            self.center + self.half_extents,
+        ))
+    }
+}
+
+/// Rectangle whose corners are rounded off with `radius`, keeping its outer half extents.
+#[derive(Debug, Clone)]
+pub struct RoundedRectangle {
+    half_extents: Vec2,
+    radius: f64,
+    center: Vec2,
+}
+
+impl RoundedRectangle {
+    pub fn new(half_extents: Vec2, radius: f64, center: Vec2) -> Self {
+        let half_extents = half_extents.abs();
+        let radius = radius.max(0.0).min(half_extents.x).min(half_extents.y);
+        Self {
+            half_extents,
+            radius,
+            center,
+        }
+    }
+}
+
+impl Sdf2d for RoundedRectangle {
+    fn distance_at(&self, p: Vec2) -> f64 {
+        let r = self.radius;
+        let inner = self.half_extents - Vec2::new(r, r);
+        rectangle_distance(p - self.center, inner) - r
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(rect(
+            self.center - self.half_extents,
+            self.center + self.half_extents,
+        ))
+    }
+}
+
+/// Distance from `q` to a rectangle around the origin.
+fn rectangle_distance(q: Vec2, half_extents: Vec2) -> f64 {
+    let d = q.abs() - half_extents;
+    d.max_component().min(0.0) + d.max(Vec2::ZERO).length()
+}
+
+/// Regular polygon with `sides` corners at `radius` from `center`, one of them pointing
+/// up along y.
+#[derive(Debug, Clone)]
+pub struct RegularPolygon {
+    radius: f64,
+    sides: usize,
+    center: Vec2,
+}
+
+impl RegularPolygon {
+    pub fn new(radius: f64, sides: usize, center: Vec2) -> Self {
+        Self {
+            radius,
+            sides: sides.max(3),
+            center,
+        }
+    }
+}
+
+impl Sdf2d for RegularPolygon {
+    fn distance_at(&self, p: Vec2) -> f64 {
+        let q = p - self.center;
+        let half_sector = PI / self.sides as f64;
+        let (sin, cos) = half_sector.sin_cos();
+
+        // Fold q into the sector around the first side, measured from the corner at +y
+        let angle = q.x.atan2(q.y).rem_euclid(2.0 * half_sector) - half_sector;
+        let length = q.length();
+        let mut q = Vec2::new(length * angle.cos(), length * angle.sin().abs())
+            - Vec2::new(cos, sin) * self.radius;
+        q.y += (-q.y).clamp(0.0, self.radius * sin);
+        q.length() * q.x.signum()
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let r = Vec2::new(self.radius, self.radius);
+        Some(rect(self.center - r, self.center + r))
+    }
+}
+
+/// Arc of a circle with `radius` around `center`, spanning `aperture` radians on either
+/// side of the +y axis, drawn with a line of half width `thickness` with round ends.
+#[derive(Debug, Clone)]
+pub struct Arc2d {
+    sin: f64,
+    cos: f64,
+    radius: f64,
+    thickness: f64,
+    center: Vec2,
+}
+
+impl Arc2d {
+    pub fn new(aperture: f64, radius: f64, thickness: f64, center: Vec2) -> Self {
+        let (sin, cos) = aperture.sin_cos();
+        Self {
+            sin,
+            cos,
+            radius,
+            thickness,
+            center,
+        }
+    }
+}
+
+impl Sdf2d for Arc2d {
+    fn distance_at(&self, p: Vec2) -> f64 {
+        let q = p - self.center;
+        let q = Vec2::new(q.x.abs(), q.y);
+        // Past the end of the arc the nearest point is the end point
+        let d = if self.cos * q.x > self.sin * q.y {
+            (q - Vec2::new(self.sin, self.cos) * self.radius).length()
+        } else {
+            (q.length() - self.radius).abs()
+        };
+        d - self.thickness
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let r = self.radius + self.thickness;
+        let r = Vec2::new(r, r);
+        Some(rect(self.center - r, self.center + r))
+    }
+}
+
+/// Rounds off the corners of a 2D SDF by moving its outline outwards by `radius`.
+#[derive(Debug, Clone)]
+pub struct Round<A> {
+    a: A,
+    radius: f64,
+}
+
+impl<A> Round<A> {
+    pub fn new(a: A, radius: f64) -> Self {
+        Self { a, radius }
+    }
+}
+
+impl<A: Sdf2d> Sdf2d for Round<A> {
+    fn distance_at(&self, p: Vec2) -> f64 {
+        self.a.distance_at(p) - self.radius
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.a.bounds().map(|b| b.expand(self.radius))
+    }
+}
+
+/// Replaces a 2D SDF by a band of half width `thickness` around its outline.
+#[derive(Debug, Clone)]
+pub struct Onion<A> {
+    a: A,
+    thickness: f64,
+}
+
+impl<A> Onion<A> {
+    pub fn new(a: A, thickness: f64) -> Self {
+        Self { a, thickness }
+    }
+}
+
+impl<A: Sdf2d> Sdf2d for Onion<A> {
+    fn distance_at(&self, p: Vec2) -> f64 {
+        self.a.distance_at(p).abs() - self.thickness
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.a.bounds().map(|b| b.expand(self.thickness))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Union<A, B> {
+    a: A,
+    b: B,
+}
+
+impl<A, B> Union<A, B> {
+    pub fn new(a: A, b: B) -> Self {
+        Self { a, b }
+    }
+}
+
+impl<A: Sdf2d, B: Sdf2d> Sdf2d for Union<A, B> {
+    fn distance_at(&self, p: Vec2) -> f64 {
+        self.a.distance_at(p).min(self.b.distance_at(p))
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(self.a.bounds()?.union(&self.b.bounds()?))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Intersect<A, B> {
+    a: A,
+    b: B,
+}
+
+impl<A, B> Intersect<A, B> {
+    pub fn new(a: A, b: B) -> Self {
+        Self { a, b }
+    }
+}
+
+impl<A: Sdf2d, B: Sdf2d> Sdf2d for Intersect<A, B> {
+    fn distance_at(&self, p: Vec2) -> f64 {
+        self.a.distance_at(p).max(self.b.distance_at(p))
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        match (self.a.bounds(), self.b.bounds()) {
+            (Some(a), Some(b)) => Some(a.intersection(&b)),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+/// Removes `b` from `a`.
+#[derive(Debug, Clone)]
+pub struct Difference<A, B> {
+    a: A,
+    b: B,
+}
+
+impl<A, B> Difference<A, B> {
+    pub fn new(a: A, b: B) -> Self {
+        Self { a, b }
+    }
+}
+
+impl<A: Sdf2d, B: Sdf2d> Sdf2d for Difference<A, B> {
+    fn distance_at(&self, p: Vec2) -> f64 {
+        self.a.distance_at(p).max(-self.b.distance_at(p))
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.a.bounds()
+    }
+}
+
+/// Solid of revolution made by turning a 2D profile around `axis`.
+///
+/// The profile's x coordinate is the distance from the axis minus `offset`, its y
+/// coordinate the position along the axis. Parts of the profile at x below `-offset` would
+/// lie beyond the axis and are never reached, but a profile that touches the axis should
+/// still be symmetric around `x = -offset` to get the right distances inside. Distances are
+/// exact if the profile's are.
+#[derive(Debug, Clone)]
+pub struct Revolve<A> {
+    a: A,
+    axis: Axis,
+    offset: f64,
+}
+
+impl<A> Revolve<A> {
+    pub fn new(a: A, axis: Axis, offset: f64) -> Self {
+        Self { a, axis, offset }
+    }
+}
+
+impl<A: Sdf2d> Sdf for Revolve<A> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let (along, u, v) = self.axis.split(&p.0);
+        let q = Vec2::new(u.hypot(v) - self.offset, along);
+        (self.a.distance_at(q), None)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let b = self.a.bounds()?;
+        let r = (self.offset + b.max.0.x).max(0.0);
+        Some(Aabb::new(
+            Point3(self.axis.join(b.min.0.y, -r, -r)),
+            Point3(self.axis.join(b.max.0.y, r, r)),
+        ))
+    }
+}
+
+/// Prism made by pushing a 2D profile out by `half_depth` on both sides along `axis`.
+///
+/// The profile lies in the plane through the origin perpendicular to `axis`, with its x and
+/// y along the plane axes in right-handed order (`y, z` for [Axis::X], `z, x` for [Axis::Y]
+/// and `x, y` for [Axis::Z]). Distances are exact if the profile's are.
+#[derive(Debug, Clone)]
+pub struct Extrude<A> {
+    a: A,
+    axis: Axis,
+    half_depth: f64,
+}
+
+impl<A> Extrude<A> {
+    pub fn new(a: A, axis: Axis, half_depth: f64) -> Self {
+        Self {
+            a,
+            axis,
+            half_depth,
+        }
+    }
+}
+
+impl<A: Sdf2d> Sdf for Extrude<A> {
+    fn value_at(&self, p: &Point3) -> (f64, Option<MaterialBlend>) {
+        let (along, u, v) = self.axis.split(&p.0);
+        let w = Vec2::new(
+            self.a.distance_at(Vec2::new(u, v)),
+            along.abs() - self.half_depth,
+        );
+        (
+            w.max_component().min(0.0) + w.max(Vec2::ZERO).length(),
+            None,
+        )
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let b = self.a.bounds()?;
+        let h = self.half_depth;
+        Some(Aabb::new(
+            Point3(self.axis.join(-h, b.min.0.x, b.min.0.y)),
+            Point3(self.axis.join(h, b.max.0.x, b.max.0.y)),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::ApproxEq;
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::scene::scenemap::sdf::primitives::{Cuboid, Cylinder, Torus};
+    use crate::test_constants::MARGIN;
+    use crate::Vec3;
+
+    #[test]
+    fn regular_polygon_is_exact() {
+        let square = RegularPolygon::new(2f64.sqrt(), 4, Vec2::ZERO);
+        // With a corner pointing up the corners lie on the axes
+        let d = |x, y| square.distance_at(Vec2::new(x, y));
+        assert!(d(3.0, 0.0).approx_eq(3.0 - 2f64.sqrt(), MARGIN));
+        assert!(d(0.0, -3.0).approx_eq(3.0 - 2f64.sqrt(), MARGIN));
+        assert!(d(2.0, 2.0).approx_eq(2.0 * 2f64.sqrt() - 1.0, MARGIN));
+        assert!(d(0.0, 0.0).approx_eq(-1.0, MARGIN));
+
+        let hexagon = RegularPolygon::new(1.0, 6, Vec2::new(1.0, 1.0));
+        let apothem = 3f64.sqrt() / 2.0;
+        assert!(hexagon
+            .distance_at(Vec2::new(4.0, 1.0))
+            .approx_eq(3.0 - apothem, MARGIN));
+    }
+
+    #[test]
+    fn arc_keeps_only_aperture() {
+        let arc = Arc2d::new(PI / 2.0, 1.0, 0.1, Vec2::new(2.0, 1.0));
+        assert!(arc.distance_at(Vec2::new(2.0, 2.0)).approx_eq(-0.1, MARGIN));
+        assert!(arc.distance_at(Vec2::new(3.0, 1.0)).approx_eq(-0.1, MARGIN));
+        // The lower half is missing, so the nearest point is the end at (3, 1)
+        assert!(arc
+            .distance_at(Vec2::new(2.0, 0.0))
+            .approx_eq(2f64.sqrt() - 0.1, MARGIN));
+    }
+
+    #[test]
+    fn booleans_combine_profiles() {
+        let a = Circle::new(1.0, Vec2::ZERO);
+        let b = Circle::new(1.0, Vec2::new(1.0, 0.0));
+        let p = Vec2::new(-0.5, 0.0);
+        assert!(Union::new(&a, &b).distance_at(p).approx_eq(-0.5, MARGIN));
+        assert!(Intersect::new(&a, &b).distance_at(p).approx_eq(0.5, MARGIN));
+        assert!(Difference::new(&a, &b)
+            .distance_at(p)
+            .approx_eq(-0.5, MARGIN));
+        assert!(Difference::new(&a, &b)
+            .distance_at(Vec2::new(0.5, 0.0))
+            .approx_eq(0.5, MARGIN));
+    }
+
+    proptest! {
+        #[test]
+        fn revolved_rectangle_is_cylinder(x in -4.0..4.0, y in -4.0..4.0, z in -4.0..4.0) {
+            let p = Point3::new(x, y, z);
+            let profile = Rectangle::new_half_extents(Vec2::new(1.0, 2.0), Vec2::ZERO);
+            let revolved = Revolve::new(profile, Axis::Y, 0.0);
+            let cylinder = Cylinder::new(1.0, 2.0, Point3::ORIGIN);
+            prop_assert!(revolved.value_at(&p).0.approx_eq(cylinder.value_at(&p).0, MARGIN));
+        }
+
+        #[test]
+        fn revolved_circle_is_torus(x in -4.0..4.0, y in -4.0..4.0, z in -4.0..4.0) {
+            let p = Point3::new(x, y, z);
+            let revolved = Revolve::new(Circle::new(0.25, Vec2::ZERO), Axis::Y, 1.0);
+            let torus = Torus::new(1.0, 0.25, Point3::ORIGIN);
+            prop_assert!(revolved.value_at(&p).0.approx_eq(torus.value_at(&p).0, MARGIN));
+        }
+
+        #[test]
+        fn extruded_shapes_match_primitives(x in -4.0..4.0, y in -4.0..4.0, z in -4.0..4.0) {
+            let p = Point3::new(x, y, z);
+            let slab = Extrude::new(Rectangle::new(Vec2::new(2.0, 4.0), Vec2::ZERO), Axis::Z, 3.0);
+            let cuboid = Cuboid::new(Vec3::new(2.0, 4.0, 6.0), Point3::ORIGIN);
+            prop_assert!(slab.value_at(&p).0.approx_eq(cuboid.value_at(&p).0, MARGIN));
+
+            let rod = Extrude::new(Circle::new(1.0, Vec2::ZERO), Axis::Y, 2.0);
+            let cylinder = Cylinder::new(1.0, 2.0, Point3::ORIGIN);
+            prop_assert!(rod.value_at(&p).0.approx_eq(cylinder.value_at(&p).0, MARGIN));
+        }
+
+        #[test]
+        fn outside_bounds_is_outside_solid(x in -6.0..6.0, y in -6.0..6.0, z in -6.0..6.0) {
+            let profile = Difference::new(
+                RoundedRectangle::new(Vec2::new(1.0, 2.0), 0.3, Vec2::new(1.5, 0.0)),
+                RegularPolygon::new(0.5, 5, Vec2::new(1.5, 1.0)),
+            );
+            let solids: [Box<dyn Sdf>; 2] = [
+                Box::new(Revolve::new(&profile, Axis::X, 0.5)),
+                Box::new(Extrude::new(&profile, Axis::Z, 1.0)),
+            ];
+            let p = Point3::new(x, y, z);
+            for solid in solids.iter() {
+                let bounds = solid.bounds().unwrap();
+                if bounds.distance_to(&p) > 0.0 {
+                    prop_assert!(solid.value_at(&p).0 > 0.0);
+                }
+            }
+        }
+    }
+}